@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX jot_links_to;
+DROP INDEX jot_links_from;
+DROP TABLE jot_links;
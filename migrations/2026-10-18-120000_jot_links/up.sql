@@ -0,0 +1,15 @@
+-- Typed edges between jots; read as "from_jot <link_type> to_jot", eg, a child's row with type
+-- 'parent' points at its parent.
+CREATE TABLE jot_links (
+       link_id BLOB NOT NULL PRIMARY KEY,
+       from_jot BLOB NOT NULL,
+       to_jot BLOB NOT NULL,
+       link_type TEXT NOT NULL,
+       device_id BLOB NOT NULL,
+       link_date TEXT,
+       FOREIGN KEY (from_jot) REFERENCES jots (jot_id) ON DELETE CASCADE ON UPDATE NO ACTION,
+       FOREIGN KEY (to_jot) REFERENCES jots (jot_id) ON DELETE CASCADE ON UPDATE NO ACTION
+);
+
+CREATE INDEX jot_links_from ON jot_links (from_jot, link_type);
+CREATE INDEX jot_links_to ON jot_links (to_jot, link_type);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER jots_fts_delete;
+DROP TRIGGER jots_fts_update;
+DROP TRIGGER jots_fts_insert;
+DROP TABLE jots_fts;
@@ -0,0 +1,28 @@
+-- Full-text index over the textual jots; the triggers keep it in step with the jots table.
+CREATE VIRTUAL TABLE jots_fts USING fts5(
+       jot_content,
+       jot_id UNINDEXED,
+       tokenize = 'porter unicode61'
+);
+
+INSERT INTO jots_fts (jot_content, jot_id)
+       SELECT CAST(jot_content AS TEXT), jot_id FROM jots WHERE jot_content_type LIKE 'text/%';
+
+CREATE TRIGGER jots_fts_insert AFTER INSERT ON jots
+       WHEN new.jot_content_type LIKE 'text/%'
+BEGIN
+       INSERT INTO jots_fts (jot_content, jot_id) VALUES (CAST(new.jot_content AS TEXT), new.jot_id);
+END;
+
+CREATE TRIGGER jots_fts_update AFTER UPDATE OF jot_content, jot_content_type ON jots
+BEGIN
+       DELETE FROM jots_fts WHERE jot_id = old.jot_id;
+       INSERT INTO jots_fts (jot_content, jot_id)
+              SELECT CAST(new.jot_content AS TEXT), new.jot_id
+              WHERE new.jot_content_type LIKE 'text/%';
+END;
+
+CREATE TRIGGER jots_fts_delete AFTER DELETE ON jots
+BEGIN
+       DELETE FROM jots_fts WHERE jot_id = old.jot_id;
+END;
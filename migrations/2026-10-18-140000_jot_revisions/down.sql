@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX jot_revisions_jot;
+DROP TABLE jot_revisions;
@@ -0,0 +1,13 @@
+-- Every version of an edited jot, including the current one, with the device that wrote it and
+-- when. Jots that have never been edited have no rows here.
+CREATE TABLE jot_revisions (
+       revision_id BLOB NOT NULL PRIMARY KEY,
+       jot_id BLOB NOT NULL,
+       revision_date TEXT NOT NULL,
+       jot_content BLOB NOT NULL,
+       jot_content_type TEXT NOT NULL,
+       device_id BLOB NOT NULL,
+       FOREIGN KEY (jot_id) REFERENCES jots (jot_id) ON DELETE CASCADE ON UPDATE NO ACTION
+);
+
+CREATE INDEX jot_revisions_jot ON jot_revisions (jot_id, revision_date);
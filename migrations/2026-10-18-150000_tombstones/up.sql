@@ -0,0 +1,7 @@
+-- Deleted jots leave a tombstone behind so that merging in another device's copy of the journal
+-- doesn't bring them back. There's deliberately no foreign key, since the jot itself is gone.
+CREATE TABLE tombstones (
+       jot_id BLOB NOT NULL PRIMARY KEY,
+       device_id BLOB NOT NULL,
+       tombstone_date TEXT NOT NULL
+);
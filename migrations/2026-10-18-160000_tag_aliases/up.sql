@@ -0,0 +1,10 @@
+-- Alternate spellings for tags; an alias given at insert time is stored as the tag it points to.
+-- The alias_id is the ID the alias text would have had as a tag.
+CREATE TABLE tag_aliases (
+       alias_id BLOB NOT NULL PRIMARY KEY,
+       alias_text TEXT NOT NULL,
+       tag_id BLOB NOT NULL,
+       device_id BLOB NOT NULL,
+       alias_date TEXT,
+       FOREIGN KEY (tag_id) REFERENCES tags (tag_id) ON DELETE CASCADE ON UPDATE NO ACTION
+);
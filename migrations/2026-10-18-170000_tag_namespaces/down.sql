@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX tags_namespace;
+ALTER TABLE tags DROP COLUMN tag_value;
+ALTER TABLE tags DROP COLUMN tag_namespace;
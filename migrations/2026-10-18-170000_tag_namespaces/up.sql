@@ -0,0 +1,16 @@
+-- Tags like "host:laptop" are split into a namespace ("host") and a value ("laptop"); other tags
+-- have no namespace and their value is the whole text. The text itself, and so the tag's ID, is
+-- unchanged.
+ALTER TABLE tags ADD COLUMN tag_namespace TEXT;
+ALTER TABLE tags ADD COLUMN tag_value TEXT NOT NULL DEFAULT '';
+
+UPDATE tags SET
+       tag_namespace = substr(tag_text, 1, instr(tag_text, ':') - 1),
+       tag_value = substr(tag_text, instr(tag_text, ':') + 1)
+WHERE instr(tag_text, ':') > 1
+  AND instr(substr(tag_text, 1, instr(tag_text, ':') - 1), ' ') = 0
+  AND length(tag_text) > instr(tag_text, ':');
+
+UPDATE tags SET tag_value = tag_text WHERE tag_namespace IS NULL;
+
+CREATE INDEX tags_namespace ON tags (tag_namespace, tag_value);
@@ -0,0 +1,19 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER jots_fts_update;
+DROP TRIGGER jots_fts_insert;
+
+CREATE TRIGGER jots_fts_insert AFTER INSERT ON jots
+       WHEN new.jot_content_type LIKE 'text/%'
+BEGIN
+       INSERT INTO jots_fts (jot_content, jot_id) VALUES (CAST(new.jot_content AS TEXT), new.jot_id);
+END;
+
+CREATE TRIGGER jots_fts_update AFTER UPDATE OF jot_content, jot_content_type ON jots
+BEGIN
+       DELETE FROM jots_fts WHERE jot_id = old.jot_id;
+       INSERT INTO jots_fts (jot_content, jot_id)
+              SELECT CAST(new.jot_content AS TEXT), new.jot_id
+              WHERE new.jot_content_type LIKE 'text/%';
+END;
+
+DROP TABLE journal_keys;
@@ -0,0 +1,33 @@
+-- Keys for sealing jot content, derived from a passphrase. Only the salt and Argon2 parameters are
+-- kept, along with a known value sealed under the key so that a wrong passphrase can be told apart.
+CREATE TABLE journal_keys (
+       key_id BLOB NOT NULL PRIMARY KEY,
+       salt BLOB NOT NULL,
+       kdf_params TEXT NOT NULL,
+       key_check BLOB NOT NULL,
+       device_id BLOB NOT NULL,
+       key_date TEXT NOT NULL
+);
+
+-- Sealed content starts with the bytes "\0jenc\1" and is no use to the full-text index, so it's
+-- left out of it.
+DELETE FROM jots_fts WHERE jot_id IN
+       (SELECT jot_id FROM jots WHERE substr(jot_content, 1, 6) = x'006a656e6301');
+
+DROP TRIGGER jots_fts_insert;
+DROP TRIGGER jots_fts_update;
+
+CREATE TRIGGER jots_fts_insert AFTER INSERT ON jots
+       WHEN new.jot_content_type LIKE 'text/%' AND substr(new.jot_content, 1, 6) != x'006a656e6301'
+BEGIN
+       INSERT INTO jots_fts (jot_content, jot_id) VALUES (CAST(new.jot_content AS TEXT), new.jot_id);
+END;
+
+CREATE TRIGGER jots_fts_update AFTER UPDATE OF jot_content, jot_content_type ON jots
+BEGIN
+       DELETE FROM jots_fts WHERE jot_id = old.jot_id;
+       INSERT INTO jots_fts (jot_content, jot_id)
+              SELECT CAST(new.jot_content AS TEXT), new.jot_id
+              WHERE new.jot_content_type LIKE 'text/%'
+                AND substr(new.jot_content, 1, 6) != x'006a656e6301';
+END;
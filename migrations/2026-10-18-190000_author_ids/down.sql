@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tags DROP COLUMN author_id;
+ALTER TABLE jots DROP COLUMN author_id;
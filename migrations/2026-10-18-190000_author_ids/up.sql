@@ -0,0 +1,4 @@
+-- Jots and tags record who wrote them, as well as on which device. Existing rows were written
+-- before there were authors, so theirs is unknown.
+ALTER TABLE jots ADD COLUMN author_id BLOB;
+ALTER TABLE tags ADD COLUMN author_id BLOB;
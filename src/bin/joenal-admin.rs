@@ -0,0 +1,510 @@
+use std::{
+    env, fs,
+    io::{self, BufReader, Write},
+    path::Path,
+    process::{self, Command},
+};
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use joenal::{
+    alias_tag, applied_migrations, create_temp_file, delete_jot, diff_lines, edit_jot,
+    encrypt_journal, export_markdown, export_ndjson, get_identity, get_jot, get_passphrase,
+    get_profile, get_revisions, get_tag_aliases, get_tags, import_jots, import_ndjson,
+    is_encrypted, jot_exists, latest_schema_version, make_pool, merge_database, merge_tags,
+    migrate_down, migrate_up, migrations, open_journal, open_pool, rename_tag, rescore_all_tags,
+    schema_version, ImportFormat,
+};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+#[async_std::main]
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("joenal-admin: {}", e);
+        process::exit(1);
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
+    let args = get_args();
+
+    let profile = get_profile(args.value_of("PROFILE"), args.value_of("DB"))?;
+    let db = &profile.db_file;
+
+    match args.subcommand() {
+        ("migrate", Some(margs)) => {
+            let conn = open_pool(db).await?;
+            migrate(&conn, margs).await?;
+            conn.close().await;
+        }
+        ("edit", Some(eargs)) => {
+            let conn = open_journal(&profile).await?;
+            edit(&conn, eargs).await?;
+            conn.close().await;
+        }
+        ("delete", Some(dargs)) => {
+            let conn = make_pool(db).await?;
+            let id = get_jot_id(&conn, dargs).await?;
+            delete_jot(&conn, id).await?;
+            println!("Deleted jot {}.", id.to_simple());
+            conn.close().await;
+        }
+        ("merge", Some(margs)) => {
+            // an encrypted journal has to be unlocked to seal what comes in
+            let conn = open_journal(&profile).await?;
+            let other = Path::new(margs.value_of("OTHER").unwrap());
+            let report = merge_database(&conn, other).await?;
+            print!("{}", report);
+            conn.close().await;
+        }
+        ("export", Some(eargs)) => {
+            let conn = open_journal(&profile).await?;
+            export(&conn, eargs).await?;
+            conn.close().await;
+        }
+        ("import", Some(iargs)) => {
+            let conn = open_journal(&profile).await?;
+            import(&conn, iargs).await?;
+            conn.close().await;
+        }
+        ("tag", Some(targs)) => {
+            let conn = make_pool(db).await?;
+            tag(&conn, targs).await?;
+            conn.close().await;
+        }
+        ("history", Some(hargs)) => {
+            let conn = open_journal(&profile).await?;
+            history(&conn, hargs).await?;
+            conn.close().await;
+        }
+        ("encrypt", Some(_)) => {
+            let conn = make_pool(db).await?;
+            // a new passphrase has to be typed twice
+            let passphrase = get_passphrase(&profile, !is_encrypted(&conn).await?)?;
+            let sealed = encrypt_journal(&conn, &passphrase).await?;
+            println!("Sealed {} jots.", sealed);
+            conn.close().await;
+        }
+        _ => unreachable!("clap requires a subcommand"),
+    }
+
+    Ok(())
+}
+
+fn get_args() -> ArgMatches<'static> {
+    App::new("Joenal")
+        .about("Manage the joenal database.")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("PROFILE")
+                .help("Use the journal of this profile from the configuration file.")
+                .long("profile")
+                .value_name("NAME")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("DB")
+                .help("Use the database at this path instead of the configured one.")
+                .long("db")
+                .value_name("PATH")
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Inspect or change the database schema version.")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("status")
+                        .about("Show which migrations have been applied."),
+                )
+                .subcommand(SubCommand::with_name("up").about("Apply all pending migrations."))
+                .subcommand(
+                    SubCommand::with_name("down")
+                        .about("Revert the most recently applied migration."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("edit")
+                .about("Edit the content of a jot in $EDITOR, keeping its ID.")
+                .arg(id_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("delete")
+                .about("Delete a jot, leaving a tombstone so that merges don't bring it back.")
+                .arg(id_arg()),
+        )
+        .subcommand(SubCommand::with_name("encrypt").about(
+            "Encrypt the journal with a passphrase, sealing the content of every jot that \
+                 isn't sealed yet.",
+        ))
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Merge another joenal database, eg, from a different device, into this one.")
+                .arg(
+                    Arg::with_name("OTHER")
+                        .help("Path to the other sqlite database.")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about(
+                    "Write the journal out as a directory of Markdown files, one per jot with \
+                     YAML front matter in year/month directories, or as an NDJSON export of \
+                     every row of the database.",
+                )
+                .arg(
+                    Arg::with_name("FORMAT")
+                        .help(
+                            "What to write; by default, an .ndjson file or '-' for stdout is \
+                             NDJSON, and anything else is a Markdown directory.",
+                        )
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["markdown", "ndjson"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("PATH")
+                        .help("The directory or file to write.")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about(
+                    "Bring in entries from a directory of Markdown files, a jrnl journal, a Day \
+                     One JSON export, or an NDJSON export; importing the same entries twice is \
+                     harmless.",
+                )
+                .arg(
+                    Arg::with_name("FORMAT")
+                        .help(
+                            "What kind of journal PATH is; by default, a directory is Markdown, a \
+                             .json file is Day One, an .ndjson file or '-' for stdin is NDJSON, \
+                             and anything else is jrnl.",
+                        )
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["markdown", "jrnl", "dayone", "ndjson"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("PATH")
+                        .help("The directory or file to import.")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tag")
+                .about("Manage tags.")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("List every tag with its score and aliases.")
+                        .arg(
+                            Arg::with_name("BY_NAME")
+                                .help("Sort by name instead of by score.")
+                                .long("by-name")
+                                .takes_value(false),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rename")
+                        .about("Give a tag new text, keeping its jots.")
+                        .arg(tag_arg("OLD", "The tag to rename."))
+                        .arg(tag_arg("NEW", "Its new text.")),
+                )
+                .subcommand(
+                    SubCommand::with_name("merge")
+                        .about("Move every jot from one tag to another, leaving an alias behind.")
+                        .arg(tag_arg("FROM", "The tag to merge away."))
+                        .arg(tag_arg("INTO", "The tag to merge it into.")),
+                )
+                .subcommand(
+                    SubCommand::with_name("alias")
+                        .about("Make one tag text stand for another when inserting jots.")
+                        .arg(tag_arg("ALIAS", "The alternate text, eg, 'js'."))
+                        .arg(tag_arg("TAG", "The tag it stands for, eg, 'javascript'.")),
+                )
+                .subcommand(
+                    SubCommand::with_name("rescore")
+                        .about("Recount every tag's score from the jots it's attached to."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("history")
+                .about("Show the revisions of a jot and what changed in each.")
+                .arg(id_arg()),
+        )
+        .get_matches()
+}
+
+fn tag_arg(name: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name)
+        .help(help)
+        .required(true)
+        .takes_value(true)
+}
+
+fn id_arg() -> Arg<'static, 'static> {
+    Arg::with_name("ID")
+        .help("ID of the jot.")
+        .required(true)
+        .takes_value(true)
+}
+
+async fn get_jot_id(conn: &SqlitePool, args: &ArgMatches<'_>) -> anyhow::Result<Uuid> {
+    let id = Uuid::parse_str(args.value_of("ID").unwrap())?;
+    if !jot_exists(conn, id).await? {
+        anyhow::bail!("no jot with ID {}", id.to_simple());
+    }
+
+    Ok(id)
+}
+
+async fn edit(conn: &SqlitePool, args: &ArgMatches<'_>) -> anyhow::Result<()> {
+    let id = get_jot_id(conn, args).await?;
+    let jot = get_jot(conn, id).await?;
+    let content = match jot.content().text() {
+        Some(text) => text.to_owned(),
+        None => anyhow::bail!("can't edit a jot of type {}", jot.content().mime_type),
+    };
+
+    let (path, mut file) = create_temp_file(".md")?;
+    file.write_all(content.as_bytes())?;
+    drop(file);
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_owned());
+    let mut editor = editor.split_whitespace();
+    let status = Command::new(editor.next().unwrap_or("vi"))
+        .args(editor)
+        .arg(&path)
+        .status();
+
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    if !status?.success() {
+        anyhow::bail!("editor exited with an error; jot left unchanged");
+    }
+
+    // editors end the last line with a newline the jot may not have had, but anything else is
+    // the user's
+    let edited = edited?;
+    let edited = match edited.strip_suffix('\n') {
+        Some(e) if !content.ends_with('\n') => e.strip_suffix('\r').unwrap_or(e),
+        _ => &edited,
+    };
+
+    if edit_jot(conn, id, edited).await? {
+        println!("Updated jot {}.", id.to_simple());
+    } else {
+        println!("No changes to jot {}.", id.to_simple());
+    }
+
+    Ok(())
+}
+
+async fn export(conn: &SqlitePool, args: &ArgMatches<'_>) -> anyhow::Result<()> {
+    let path = args.value_of("PATH").unwrap();
+
+    if is_ndjson(args.value_of("FORMAT"), path) {
+        if path == "-" {
+            let _ = export_ndjson(conn, &mut io::stdout().lock()).await?;
+        } else {
+            let mut out = io::BufWriter::new(fs::File::create(path)?);
+            let count = export_ndjson(conn, &mut out).await?;
+            println!("Exported {} record(s) to {}.", count, path);
+        }
+        return Ok(());
+    }
+
+    let count = export_markdown(conn, Path::new(path)).await?;
+    println!("Exported {} jot(s) to {}.", count, path);
+
+    Ok(())
+}
+
+// Whether `--format` asks for NDJSON, or, without it, whether the path looks like an NDJSON file.
+fn is_ndjson(format: Option<&str>, path: &str) -> bool {
+    match format {
+        Some(format) => format == "ndjson",
+        None => path == "-" || path.ends_with(".ndjson") || path.ends_with(".jsonl"),
+    }
+}
+
+async fn import(conn: &SqlitePool, args: &ArgMatches<'_>) -> anyhow::Result<()> {
+    let path = args.value_of("PATH").unwrap();
+
+    if is_ndjson(args.value_of("FORMAT"), path) {
+        let report = if path == "-" {
+            import_ndjson(conn, io::stdin().lock()).await?
+        } else {
+            import_ndjson(conn, BufReader::new(fs::File::open(path)?)).await?
+        };
+        print!("{}", report);
+        return Ok(());
+    }
+
+    let path = Path::new(path);
+    let format = match args.value_of("FORMAT") {
+        Some(f) => f.parse().map_err(anyhow::Error::msg)?,
+        None => ImportFormat::guess(path),
+    };
+    let report = import_jots(conn, &get_identity()?, path, format).await?;
+    print!("{}", report);
+
+    Ok(())
+}
+
+async fn tag(conn: &SqlitePool, args: &ArgMatches<'_>) -> anyhow::Result<()> {
+    match args.subcommand() {
+        ("list", Some(largs)) => {
+            let mut tags = get_tags(conn).await?;
+            if largs.is_present("BY_NAME") {
+                tags.sort_by(|a, b| a.text().cmp(b.text()));
+            }
+            let aliases = get_tag_aliases(conn).await?;
+
+            for tag in tags.iter() {
+                let also: Vec<&str> = aliases
+                    .iter()
+                    .filter(|a| a.tag_id() == tag.id())
+                    .map(|a| a.text())
+                    .collect();
+                if also.is_empty() {
+                    println!("{}", tag);
+                } else {
+                    println!("{} (also: {})", tag, also.join(", "));
+                }
+            }
+        }
+        ("rename", Some(rargs)) => {
+            let (old, new) = (
+                rargs.value_of("OLD").unwrap(),
+                rargs.value_of("NEW").unwrap(),
+            );
+            rename_tag(conn, &get_identity()?, old, new).await?;
+            println!("Renamed '{}' to '{}'.", old, new);
+        }
+        ("merge", Some(margs)) => {
+            let (from, into) = (
+                margs.value_of("FROM").unwrap(),
+                margs.value_of("INTO").unwrap(),
+            );
+            merge_tags(conn, &get_identity()?, from, into).await?;
+            println!("Merged '{}' into '{}'.", from, into);
+        }
+        ("alias", Some(aargs)) => {
+            let (alias, tag) = (
+                aargs.value_of("ALIAS").unwrap(),
+                aargs.value_of("TAG").unwrap(),
+            );
+            alias_tag(conn, &get_identity()?, alias, tag).await?;
+            println!("'{}' now stands for '{}'.", alias, tag);
+        }
+        ("rescore", Some(_)) => {
+            let changed = rescore_all_tags(conn).await?;
+            println!("Corrected the score of {} tag(s).", changed);
+        }
+        _ => unreachable!("clap requires a subcommand"),
+    }
+
+    Ok(())
+}
+
+async fn history(conn: &SqlitePool, args: &ArgMatches<'_>) -> anyhow::Result<()> {
+    let id = get_jot_id(conn, args).await?;
+    let revisions = get_revisions(conn, id).await?;
+
+    if revisions.is_empty() {
+        println!("Jot {} has never been edited.\n", id.to_simple());
+        println!("{}", get_jot(conn, id).await?);
+        return Ok(());
+    }
+
+    let mut previous = String::new();
+    for (i, rev) in revisions.iter().enumerate() {
+        let content = rev.content().to_string();
+
+        println!(
+            "Revision {} of {}, {} from device {}\n",
+            i + 1,
+            revisions.len(),
+            rev.date().to_rfc3339(),
+            rev.device().to_simple()
+        );
+        // the original has nothing to compare against, so show it as it was
+        if i == 0 {
+            println!("{}", content);
+        } else {
+            for line in diff_lines(&previous, &content) {
+                println!("{}", line);
+            }
+        }
+        println!();
+
+        previous = content;
+    }
+
+    Ok(())
+}
+
+async fn migrate(conn: &SqlitePool, args: &ArgMatches<'_>) -> anyhow::Result<()> {
+    match args.subcommand_name() {
+        Some("status") => {
+            let applied = applied_migrations(conn).await?;
+            for m in migrations() {
+                match applied.iter().find(|(v, _, _)| *v == m.version) {
+                    Some((_, _, date)) => {
+                        println!(
+                            "{:>4} {:<24} applied {}",
+                            m.version,
+                            m.name,
+                            date.to_rfc3339()
+                        )
+                    }
+                    None => println!("{:>4} {:<24} pending", m.version, m.name),
+                }
+            }
+
+            let version = schema_version(conn).await?;
+            let latest = latest_schema_version();
+            if version > latest {
+                println!(
+                    "\nDatabase is at version {}, newer than this joenal understands ({}).",
+                    version, latest
+                );
+            }
+        }
+        Some("up") => {
+            let version = schema_version(conn).await?;
+            if version > latest_schema_version() {
+                anyhow::bail!(
+                    "database is at version {}, newer than this joenal understands",
+                    version
+                );
+            }
+            let applied = migrate_up(conn).await?;
+            if applied.is_empty() {
+                println!("Database is up to date at version {}.", version);
+            }
+            for v in applied {
+                println!("Applied migration {}.", v);
+            }
+        }
+        Some("down") => match migrate_down(conn).await? {
+            Some(v) => println!("Reverted migration {}.", v),
+            None => println!("No migrations to revert."),
+        },
+        _ => unreachable!("clap requires a subcommand"),
+    }
+
+    Ok(())
+}
@@ -12,57 +12,256 @@
 
 //! An example of live markdown preview
 
-use std::sync::Arc;
+use std::{process, sync::Arc};
 
+use clap::{App, Arg, ArgMatches};
 use druid::{
-    widget::{Label, LineBreaking, List, Painter, RawLabel, Scroll, Split},
+    widget::{
+        Button, Either, Flex, Label, LineBreaking, List, Painter, RawLabel, Scroll, SizedBox,
+        Split, TextBox,
+    },
     AppLauncher, Color, LocalizedString, UnitPoint, Widget, WidgetExt, WindowDesc,
 };
-use joenal::{get_config, get_jots, gui::*, make_pool};
+use joenal::{
+    get_config, get_jot, get_profile, get_tags, gui::*, list_jots, open_journal, JotFilter,
+};
 
 const WINDOW_TITLE: LocalizedString<AppState> = LocalizedString::new("Joenal");
 
 const SPACER_SIZE: f64 = 8.0;
 
 #[async_std::main]
-async fn main() -> anyhow::Result<()> {
-    let config = get_config();
-    std::env::set_var("DATABASE_URL", config.db_file);
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("joenal-gui: {}", e);
+        process::exit(1);
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
+    let args = get_args();
+    let profile = get_profile(args.value_of("PROFILE"), args.value_of("DB"))?;
+    let config = get_config()?;
+    let keys = KeyController::new(&config.shortcuts)?;
 
-    let conn = make_pool().await;
+    let conn = open_journal(&profile).await?;
 
-    // insert_jot(&conn, &jot);
+    // only the first page of jots is listed, and only the one being shown is loaded in full
+    let jots = list_jots(&conn, &JotFilter::default(), None, PAGE_SIZE).await?;
 
-    let jots = get_jots(&conn).await;
-    let jot = &jots[0];
+    let tags = get_tags(&conn).await?;
 
-    // just between us friends, we don't have any non-utf8 bytes in our content yet
-    let content: &str = std::str::from_utf8(jot.content().bytes).unwrap();
+    // attachments are shown as a placeholder rather than rendered
+    let content = match jots.first() {
+        Some(first) => get_jot(&conn, first.id()).await?.content().to_string(),
+        None => String::new(),
+    };
 
     // describe the main window
-    let main_window = WindowDesc::new(build_root_widget())
+    let main_window = WindowDesc::new(build_root_widget(keys))
         .title(WINDOW_TITLE)
         .window_size((700.0, 600.0));
 
+    let launcher = AppLauncher::with_window(main_window)
+        .log_to_console()
+        .delegate(Delegate);
+
     let initial_state = AppState::new(
-        rebuild_rendered_text(content),
+        rebuild_rendered_text(&content),
         0,
         conn.clone(),
         Arc::new(jots),
+        profile,
+        config.identity()?,
+        tags,
+        launcher.get_external_handle(),
     );
 
     // start the application
-    AppLauncher::with_window(main_window)
-        .log_to_console()
-        .delegate(Delegate)
+    launcher
         .launch(initial_state)
         .expect("Failed to launch application");
 
     Ok(())
 }
 
-fn build_root_widget() -> impl Widget<AppState> {
-    let rendered = Scroll::new(
+fn get_args() -> ArgMatches<'static> {
+    App::new("Joenal GUI")
+        .about("Browse the joenal database.")
+        .arg(
+            Arg::with_name("PROFILE")
+                .help("Use the journal of this profile from the configuration file.")
+                .long("profile")
+                .value_name("NAME")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("DB")
+                .help("Use the database at this path instead of the configured one.")
+                .long("db")
+                .value_name("PATH")
+                .takes_value(true),
+        )
+        .get_matches()
+}
+
+fn build_root_widget(keys: KeyController) -> impl Widget<AppState> {
+    let cards = List::new(|| {
+        let label = Label::new(|item: &JotCard, _env: &_| item.label().to_string())
+            .align_vertical(UnitPoint::LEFT)
+            .padding(10.0)
+            .expand()
+            .height(50.0)
+            .border(Color::rgb8(0, 0, 0), 2.0)
+            .background(Painter::new(jot_card_background));
+        label.on_click(|_event_ctx, data, _env| data.make_current())
+    });
+
+    let more = Either::new(
+        |data: &AppState, _env| data.has_more(),
+        Button::new("Show older jots")
+            .on_click(|_ctx, data: &mut AppState, _env| data.load_more())
+            .padding(SPACER_SIZE),
+        SizedBox::empty(),
+    );
+
+    let new_jot = Button::new("New jot")
+        .on_click(|_ctx, data: &mut AppState, _env| data.start_composing())
+        .padding(SPACER_SIZE);
+
+    let search = TextBox::new()
+        .with_placeholder("Search")
+        .lens(AppState::search)
+        .controller(SearchController)
+        .expand_width()
+        .padding(SPACER_SIZE / 2.0);
+
+    let jotbox = Flex::column()
+        .with_child(search)
+        .with_child(build_filter_bar())
+        .with_flex_child(
+            Scroll::new(
+                Flex::column()
+                    .with_child(new_jot)
+                    .with_child(cards)
+                    .with_child(more),
+            )
+            .vertical(),
+            1.0,
+        );
+
+    let viewer = Either::new(
+        |data: &AppState, _env| data.is_editing(),
+        build_edit_pane(),
+        build_jot_view(),
+    );
+
+    let right = Either::new(
+        |data: &AppState, _env| data.is_composing(),
+        build_compose_pane(),
+        viewer,
+    );
+
+    let jots = Split::columns(jotbox, right).draggable(true);
+
+    let main = Split::columns(build_tag_sidebar(), jots)
+        .split_point(0.2)
+        .draggable(true)
+        .controller(RichTextRebuilder);
+
+    Flex::column()
+        .with_child(build_palette())
+        .with_flex_child(main, 1.0)
+        .controller(keys)
+}
+
+// a field to type a command's name in, and the commands that match it
+fn build_palette() -> impl Widget<AppState> {
+    let query = TextBox::new()
+        .with_placeholder("Type a command")
+        .lens(AppState::palette_query)
+        .controller(PaletteController)
+        .expand_width();
+
+    let entries = List::new(|| {
+        Label::new(|item: &PaletteEntry, _env: &_| item.label().to_string())
+            .padding(SPACER_SIZE / 2.0)
+            .expand_width()
+            .background(Painter::new(palette_entry_background))
+            .on_click(|ctx, data: &mut PaletteEntry, _env| {
+                ctx.submit_command(RUN_ACTION.with(data.action()))
+            })
+    });
+
+    Either::new(
+        |data: &AppState, _env| data.is_palette_open(),
+        Flex::column()
+            .with_child(query)
+            .with_child(entries)
+            .padding(SPACER_SIZE),
+        SizedBox::empty(),
+    )
+}
+
+// every tag with its count, most used or alphabetically
+fn build_tag_sidebar() -> impl Widget<AppState> {
+    let order = Button::dynamic(|data: &AppState, _env| {
+        if data.tags_by_name() {
+            "Sort by count".to_string()
+        } else {
+            "Sort by name".to_string()
+        }
+    })
+    .on_click(|_ctx, data: &mut AppState, _env| data.toggle_tag_order())
+    .padding(SPACER_SIZE);
+
+    let rows = List::new(|| {
+        Label::new(|item: &TagRow, _env: &_| item.label())
+            .align_vertical(UnitPoint::LEFT)
+            .padding(SPACER_SIZE / 2.0)
+            .expand_width()
+            .background(Painter::new(tag_row_background))
+            .on_click(|_event_ctx, data: &mut TagRow, _env| data.toggle())
+    });
+
+    Flex::column()
+        .with_child(order)
+        .with_flex_child(Scroll::new(rows).vertical(), 1.0)
+}
+
+// the tags the jots are filtered by, each removable, and whether jots need all of them or any
+fn build_filter_bar() -> impl Widget<AppState> {
+    let chips = List::new(|| {
+        Label::new(|item: &TagChip, _env: &_| item.label())
+            .padding((SPACER_SIZE, SPACER_SIZE / 2.0))
+            .background(Painter::new(tag_chip_background))
+            .padding(SPACER_SIZE / 2.0)
+            .on_click(|_event_ctx, data: &mut TagChip, _env| data.remove())
+    })
+    .horizontal();
+
+    let mode = Button::dynamic(|data: &AppState, _env| {
+        if data.is_matching_any() {
+            "Any of".to_string()
+        } else {
+            "All of".to_string()
+        }
+    })
+    .on_click(|_ctx, data: &mut AppState, _env| data.toggle_match_any());
+
+    Either::new(
+        |data: &AppState, _env| data.is_filtered(),
+        Flex::row()
+            .with_child(mode)
+            .with_flex_child(Scroll::new(chips).horizontal(), 1.0)
+            .padding(SPACER_SIZE / 2.0),
+        SizedBox::empty(),
+    )
+}
+
+// the rendered Markdown of the current jot, or of the draft while composing
+fn build_preview() -> impl Widget<AppState> {
+    Scroll::new(
         RawLabel::new()
             .with_text_color(Color::BLACK)
             .with_line_break_mode(LineBreaking::WordWrap)
@@ -72,19 +271,88 @@ fn build_root_widget() -> impl Widget<AppState> {
     )
     .vertical()
     .background(Color::grey8(222))
-    .expand();
+    .expand()
+}
 
-    let jotbox = Scroll::new(List::new(|| {
-        let label = Label::new(|item: &JotCard, _env: &_| item.label().to_string())
-            .align_vertical(UnitPoint::LEFT)
-            .padding(10.0)
-            .expand()
-            .height(50.0)
-            .border(Color::rgb8(0, 0, 0), 2.0)
-            .background(Painter::new(jot_card_background));
-        label.on_click(|_event_ctx, data, _env| data.make_current())
-    }))
-    .vertical();
+// the current jot, with a button to edit it if it's text, and whatever last went wrong
+fn build_jot_view() -> impl Widget<AppState> {
+    let edit = Either::new(
+        |data: &AppState, _env| data.can_edit(),
+        Button::new("Edit")
+            .on_click(|_ctx, data: &mut AppState, _env| data.start_editing())
+            .padding(SPACER_SIZE),
+        SizedBox::empty(),
+    );
+
+    let status = Label::new(|data: &AppState, _env: &_| data.status().to_string())
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .padding(SPACER_SIZE);
+
+    Flex::column()
+        .with_child(Flex::row().with_child(edit).with_flex_child(status, 1.0))
+        .with_flex_child(build_preview(), 1.0)
+}
+
+// the current jot's Markdown beside its preview
+fn build_edit_pane() -> impl Widget<AppState> {
+    let editor = TextBox::multiline().lens(AppState::edit_text).expand();
+
+    let status = Label::new(|data: &AppState, _env: &_| {
+        if !data.status().is_empty() {
+            data.status().to_string()
+        } else if data.is_dirty() {
+            "Unsaved changes".to_string()
+        } else {
+            "No changes".to_string()
+        }
+    });
+
+    let buttons = Flex::row()
+        .with_child(
+            Button::new("Save").on_click(|_ctx, data: &mut AppState, _env| data.save_edit()),
+        )
+        .with_spacer(SPACER_SIZE)
+        .with_child(
+            Button::new("Cancel").on_click(|_ctx, data: &mut AppState, _env| data.cancel_editing()),
+        )
+        .with_spacer(SPACER_SIZE)
+        .with_child(status)
+        .padding(SPACER_SIZE);
+
+    Flex::column()
+        .with_flex_child(Split::columns(editor, build_preview()).draggable(true), 1.0)
+        .with_child(buttons)
+}
+
+fn build_compose_pane() -> impl Widget<AppState> {
+    let tags = TextBox::new()
+        .with_placeholder("Tags, separated by commas")
+        .lens(AppState::draft_tags)
+        .expand_width()
+        .padding(SPACER_SIZE);
+
+    let editor = TextBox::multiline()
+        .with_placeholder("Write your jot in Markdown")
+        .lens(AppState::draft)
+        .expand();
+
+    let buttons = Flex::row()
+        .with_child(
+            Button::new("Save").on_click(|_ctx, data: &mut AppState, _env| data.save_draft()),
+        )
+        .with_spacer(SPACER_SIZE)
+        .with_child(
+            Button::new("Cancel")
+                .on_click(|_ctx, data: &mut AppState, _env| data.cancel_composing()),
+        )
+        .with_spacer(SPACER_SIZE)
+        .with_child(Label::new(|data: &AppState, _env: &_| {
+            data.status().to_string()
+        }))
+        .padding(SPACER_SIZE);
 
-    Split::columns(jotbox, rendered).draggable(true)
+    Flex::column()
+        .with_child(tags)
+        .with_flex_child(Split::rows(editor, build_preview()).draggable(true), 1.0)
+        .with_child(buttons)
 }
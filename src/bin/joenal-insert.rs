@@ -1,18 +1,38 @@
 use std::{
-    env,
+    fs,
     io::{prelude::*, stdin},
+    path::Path,
+    process,
 };
 
 use chrono::prelude::*;
 use clap::{App, Arg, ArgMatches};
-use joenal::{get_config, insert_jot, make_pool, parse_tags, RawJot};
+use joenal::{
+    get_identity, get_profile, insert_attachment, insert_jot, insert_link, jot_exists,
+    open_journal, parse_tags, sniff_mime, LinkType, RawJot,
+};
+use uuid::Uuid;
 
 #[async_std::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("joenal-insert: {}", e);
+        process::exit(1);
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
     let args = get_args();
+    let parent = match args.value_of("PARENT") {
+        Some(p) => Some(Uuid::parse_str(p)?),
+        None => None,
+    };
+    let attachments = get_attachments(&args)?;
     let creation_date = Utc::now();
     let content = get_content(&args);
-    let tags = get_tags(&args);
+    let profile = get_profile(args.value_of("PROFILE"), args.value_of("DB"))?;
+    let identity = get_identity()?;
+    let tags = profile.jot_tags(get_tags(&args));
 
     let jot = RawJot {
         content,
@@ -20,12 +40,23 @@ async fn main() -> anyhow::Result<()> {
         tags,
     };
 
-    let config = get_config();
-    env::set_var("DATABASE_URL", &config.db_file);
+    let conn = open_journal(&profile).await?;
+
+    if let Some(parent) = parent {
+        if !jot_exists(&conn, parent).await? {
+            anyhow::bail!("no parent jot with ID {}", parent.to_simple());
+        }
+    }
+
+    let jot_id = insert_jot(&conn, &identity, &jot).await?;
 
-    let conn = make_pool().await;
+    if let Some(parent) = parent {
+        insert_link(&conn, jot_id, parent, LinkType::Parent).await?;
+    }
 
-    insert_jot(&conn, &jot).await?;
+    for (bytes, mime_type) in attachments.iter() {
+        insert_attachment(&conn, &identity, jot_id, bytes, mime_type, creation_date).await?;
+    }
 
     conn.close().await;
     Ok(())
@@ -55,9 +86,56 @@ fn get_args() -> ArgMatches<'static> {
                 .long("message")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("ATTACH")
+                .help("Attach a file to the entry; may be specified more than once.")
+                .short("a")
+                .long("attach")
+                .value_name("FILE")
+                .multiple(true)
+                .number_of_values(1)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("PARENT")
+                .help("ID of the jot this entry is a child of.")
+                .short("p")
+                .long("parent")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("PROFILE")
+                .help("Use the journal of this profile from the configuration file.")
+                .long("profile")
+                .value_name("NAME")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("DB")
+                .help("Use the database at this path instead of the configured one.")
+                .long("db")
+                .value_name("PATH")
+                .takes_value(true),
+        )
         .get_matches()
 }
 
+// read the attachments up front so that a bad path doesn't leave a half-inserted entry behind
+fn get_attachments(args: &ArgMatches<'_>) -> anyhow::Result<Vec<(Vec<u8>, String)>> {
+    let mut attachments = Vec::new();
+
+    if let Some(paths) = args.values_of("ATTACH") {
+        for path in paths.map(Path::new) {
+            let bytes = fs::read(path)
+                .map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+            let mime_type = sniff_mime(path, &bytes);
+            attachments.push((bytes, mime_type));
+        }
+    }
+
+    Ok(attachments)
+}
+
 fn get_content(args: &ArgMatches<'_>) -> String {
     let mut content = String::new();
 
@@ -95,8 +173,5 @@ fn get_tags(args: &ArgMatches<'_>) -> Vec<String> {
         }
     }
 
-    if tags.is_empty() {
-        tags.push("untagged".to_owned());
-    }
     tags
 }
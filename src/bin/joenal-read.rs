@@ -1,28 +1,147 @@
-use std::env;
+use std::{collections::HashMap, process};
 
 use clap::{App, Arg, ArgMatches};
-use joenal::{get_config, get_jots, make_pool};
+use joenal::{
+    get_ancestors, get_children, get_jot_page, get_profile, get_tags_for_jots, open_journal,
+    parse_date_span, search_jots, DateSpan, Jot, JotFilter, JotFormat, TagFilter,
+};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+// how many jots to have in memory at once
+const PAGE_SIZE: u32 = 500;
 
 #[async_std::main]
-async fn main() -> anyhow::Result<()> {
-    let _args = get_args();
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("joenal-read: {}", e);
+        process::exit(1);
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
+    let args = get_args();
 
-    let config = get_config();
-    env::set_var("DATABASE_URL", config.db_file);
+    let profile = get_profile(args.value_of("PROFILE"), args.value_of("DB"))?;
 
-    let conn = make_pool().await;
+    let format = get_format(&args)?;
+
+    let conn = open_journal(&profile).await?;
 
     // insert_jot(&conn, &jot);
 
-    let jots = get_jots(&conn).await;
+    if let Some(header) = format.header() {
+        println!("{}", header);
+    }
+
+    if let Some(search) = args.value_of("SEARCH") {
+        let hits = search_jots(&conn, search).await?;
+        if format == JotFormat::Plain {
+            for hit in hits.iter() {
+                println!("{}", hit);
+            }
+        } else {
+            let jots: Vec<Jot> = hits.into_iter().map(|hit| hit.jot).collect();
+            print(&conn, &format, &jots).await?;
+        }
+        return Ok(());
+    }
+
+    if let Some(id) = args.value_of("CHILDREN") {
+        print(
+            &conn,
+            &format,
+            &get_children(&conn, Uuid::parse_str(id)?).await?,
+        )
+        .await?;
+        return Ok(());
+    } else if let Some(id) = args.value_of("ANCESTORS") {
+        print(
+            &conn,
+            &format,
+            &get_ancestors(&conn, Uuid::parse_str(id)?).await?,
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // print a page at a time so that a big journal never has to fit in memory
+    let filter = get_filter(&args)?;
+    let mut remaining = match args.value_of("LIMIT") {
+        Some(limit) => limit.parse()?,
+        None => u64::MAX,
+    };
+    let mut cursor = None;
+    while remaining > 0 {
+        let size = remaining.min(PAGE_SIZE as u64) as u32;
+        let page = get_jot_page(&conn, &filter, cursor.as_ref(), size).await?;
+        print(&conn, &format, &page).await?;
+
+        remaining -= page.len() as u64;
+        match page.last() {
+            Some(last) if page.len() as u32 == size => cursor = Some(last.cursor()),
+            _ => break,
+        }
+    }
+
+    Ok(())
+}
+
+// Print the jots with their tags, which are looked up for just these jots; the plain format is
+// the only one without tags.
+async fn print(conn: &SqlitePool, format: &JotFormat, jots: &[Jot]) -> anyhow::Result<()> {
+    let tags = match format {
+        JotFormat::Plain => HashMap::new(),
+        _ => {
+            let ids: Vec<Uuid> = jots.iter().map(|j| j.id()).collect();
+            get_tags_for_jots(conn, &ids).await?
+        }
+    };
+    let no_tags = Vec::new();
 
     for j in jots.iter() {
-        println!("{}", &j);
+        let jot_tags = tags.get(&j.id()).unwrap_or(&no_tags);
+        println!("{}", format.render(j, jot_tags));
     }
 
     Ok(())
 }
 
+fn get_filter(args: &ArgMatches<'_>) -> anyhow::Result<JotFilter> {
+    let tags = match args.values_of("TAGS") {
+        Some(tags) => TagFilter::parse(tags, args.is_present("ANY")),
+        None => TagFilter::default(),
+    };
+
+    // "--since yesterday" starts at the beginning of yesterday, and "--until yesterday" takes in
+    // all of it, as "--until <timestamp>" does the timestamp itself
+    let since = args.value_of("SINCE").map(get_date_span).transpose()?;
+    let until = args.value_of("UNTIL").map(get_date_span).transpose()?;
+
+    Ok(JotFilter {
+        tags,
+        since: since.map(|s| s.start),
+        until,
+    })
+}
+
+fn get_format(args: &ArgMatches<'_>) -> anyhow::Result<JotFormat> {
+    if let Some(template) = args.value_of("TEMPLATE") {
+        return Ok(JotFormat::Template(
+            template.parse().map_err(anyhow::Error::msg)?,
+        ));
+    }
+
+    match args.value_of("FORMAT") {
+        Some(format) => format.parse().map_err(anyhow::Error::msg),
+        None => Ok(JotFormat::Plain),
+    }
+}
+
+fn get_date_span(date: &str) -> anyhow::Result<DateSpan> {
+    parse_date_span(date).ok_or_else(|| anyhow::anyhow!("could not understand the date '{}'", date))
+}
+
 fn get_args() -> ArgMatches<'static> {
     App::new("Jotlog Insert")
         .about("Create and insert an entry into the joenal database.")
@@ -34,11 +153,43 @@ fn get_args() -> ArgMatches<'static> {
         )
         .arg(
             Arg::with_name("TAGS")
-                .help("Add tag to entry; may be specified more than once for more than one tag.")
+                .help(
+                    "Only show jots with this tag, or without it if prefixed with '!'; may be \
+                     specified more than once, and jots must match every tag. Tags may be \
+                     patterns like 'host:*' or 'pwd:~/src/**'.",
+                )
                 .short("t")
                 .long("tag")
                 .multiple(true)
-                .takes_value(true),
+                .number_of_values(1)
+                .takes_value(true)
+                .conflicts_with_all(&["SEARCH", "CHILDREN", "ANCESTORS"]),
+        )
+        .arg(
+            Arg::with_name("ANY")
+                .help("Show jots with any of the given tags, rather than all of them.")
+                .long("any")
+                .takes_value(false)
+                .requires("TAGS"),
+        )
+        .arg(
+            Arg::with_name("SINCE")
+                .help(
+                    "Only show jots created on or after this date; accepts RFC3339, YYYY-MM-DD, \
+                     or phrases like 'yesterday', 'last monday' or '3 weeks ago'.",
+                )
+                .long("since")
+                .value_name("DATE")
+                .takes_value(true)
+                .conflicts_with_all(&["SEARCH", "CHILDREN", "ANCESTORS"]),
+        )
+        .arg(
+            Arg::with_name("UNTIL")
+                .help("Only show jots created up to and including this date.")
+                .long("until")
+                .value_name("DATE")
+                .takes_value(true)
+                .conflicts_with_all(&["SEARCH", "CHILDREN", "ANCESTORS"]),
         )
         .arg(
             Arg::with_name("MESSAGE")
@@ -47,5 +198,74 @@ fn get_args() -> ArgMatches<'static> {
                 .long("message")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("SEARCH")
+                .help("Show the jots containing all the given words, best match first.")
+                .short("s")
+                .long("search")
+                .value_name("WORDS")
+                .takes_value(true)
+                .conflicts_with_all(&["CHILDREN", "ANCESTORS"]),
+        )
+        .arg(
+            Arg::with_name("CHILDREN")
+                .help("Show the jots whose parent is the given jot.")
+                .long("children")
+                .value_name("ID")
+                .takes_value(true)
+                .conflicts_with("ANCESTORS"),
+        )
+        .arg(
+            Arg::with_name("LIMIT")
+                .help("Show at most this many jots, newest first.")
+                .short("n")
+                .long("limit")
+                .value_name("COUNT")
+                .takes_value(true)
+                .conflicts_with_all(&["SEARCH", "CHILDREN", "ANCESTORS"]),
+        )
+        .arg(
+            Arg::with_name("FORMAT")
+                .help(
+                    "How to print the jots: 'json' gives one object per line, 'csv' starts with \
+                     a header row, and 'oneline' puts each jot on a single line.",
+                )
+                .short("f")
+                .long("format")
+                .possible_values(&["plain", "json", "markdown", "oneline", "csv"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("TEMPLATE")
+                .help(
+                    "Print each jot with this template instead, eg, '{date}\\t{tags}\\t{content}'; \
+                     the placeholders are {id}, {short_id}, {date}, {device}, {tags}, \
+                     {content_type} and {content}.",
+                )
+                .long("template")
+                .takes_value(true)
+                .conflicts_with("FORMAT"),
+        )
+        .arg(
+            Arg::with_name("ANCESTORS")
+                .help("Show the chain of parents above the given jot, nearest first.")
+                .long("ancestors")
+                .value_name("ID")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("PROFILE")
+                .help("Use the journal of this profile from the configuration file.")
+                .long("profile")
+                .value_name("NAME")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("DB")
+                .help("Use the database at this path instead of the configured one.")
+                .long("db")
+                .value_name("PATH")
+                .takes_value(true),
+        )
         .get_matches()
 }
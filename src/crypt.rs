@@ -0,0 +1,450 @@
+use std::{collections::HashMap, env, process::Command, sync::RwLock};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use sha2::Sha256;
+use sqlx::{query, query_as, query_scalar, sqlite::SqliteConnection, SqlitePool};
+
+use super::{get_device_id, make_pool, models::JournalKey, Error, Profile, Result, Uuid};
+
+// Sealed content is these bytes (a NUL, "jenc" and a format version), then the ID of the key it was
+// sealed with, the nonce, and the ciphertext with its tag. The journal_keys migration leaves
+// anything starting with them out of the full-text index.
+const SEAL_MAGIC: &[u8] = b"\0jenc\x01";
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = SEAL_MAGIC.len() + 16 + NONCE_LEN;
+
+// sealed under each key, as if for the nil jot ID, so that a passphrase can be checked
+const KEY_CHECK: &[u8] = b"joenal key check";
+
+// Argon2id with the parameters recommended by OWASP
+const KDF_PARAMS: &str = "argon2id$v=19$m=19456,t=2,p=1";
+
+/// Where the binaries look for the passphrase of an encrypted journal before asking for it.
+pub const PASSPHRASE_VAR: &str = "JOENAL_PASSPHRASE";
+
+lazy_static! {
+    // the keys of the journals this process has unlocked, by key ID
+    static ref KEYRING: RwLock<HashMap<Uuid, Key>> = RwLock::new(HashMap::new());
+}
+
+// The two keys derived from a passphrase: one for the nonces and one for the cipher, so that neither
+// use of the key can leak anything about the other.
+#[derive(Clone)]
+pub(crate) struct Key {
+    id: Uuid,
+    nonce_key: [u8; 32],
+    cipher_key: [u8; 32],
+}
+
+/// Whether the journal has a key, so that new content is sealed and a passphrase is needed to read
+/// it.
+pub async fn is_encrypted(conn: &SqlitePool) -> Result<bool> {
+    let keys: u32 = query_scalar("SELECT COUNT(*) FROM journal_keys")
+        .fetch_one(conn)
+        .await?;
+
+    Ok(keys > 0)
+}
+
+/// Unlock the journal's content with the passphrase, for the rest of the process. Fails with
+/// `Error::Locked` if the passphrase fits none of the journal's keys.
+pub async fn unlock(conn: &SqlitePool, passphrase: &str) -> Result<()> {
+    let keys: Vec<JournalKey> = query_as("SELECT * FROM journal_keys")
+        .fetch_all(conn)
+        .await?;
+
+    let mut unlocked = 0;
+    for record in keys.iter() {
+        let key = derive_key(record.id(), passphrase, record.salt(), record.kdf_params())?;
+        if open(&key, Uuid::nil(), record.key_check()).as_deref() == Some(KEY_CHECK) {
+            add_key(key);
+            unlocked += 1;
+        }
+    }
+
+    if unlocked == 0 && !keys.is_empty() {
+        return Err(Error::Locked(
+            "wrong passphrase for this journal".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Seal the content of every jot and revision that isn't sealed yet, making a key from the
+/// passphrase first if the journal doesn't have one. If it does, the passphrase has to fit it. The
+/// journal is unlocked afterwards. Returns the number of jots that were sealed.
+pub async fn encrypt_journal(pool: &SqlitePool, passphrase: &str) -> Result<usize> {
+    if is_encrypted(pool).await? {
+        unlock(pool, passphrase).await?;
+    } else {
+        let salt: [u8; 16] = rand::random();
+        let key = derive_key(Uuid::new_v4(), passphrase, &salt, KDF_PARAMS)?;
+
+        let record = JournalKey::new(
+            key.id,
+            salt.to_vec(),
+            KDF_PARAMS.to_owned(),
+            seal(&key, Uuid::nil(), KEY_CHECK)?,
+            get_device_id()?,
+            chrono::Utc::now(),
+        );
+        let _ = record.as_insert().execute(pool).await?;
+        add_key(key);
+    }
+
+    let mut tx = pool.begin().await?;
+    let key = match sealing_key(&mut tx).await? {
+        Some(key) => key,
+        None => return Err(Error::Locked("the journal has no key".to_owned())),
+    };
+    let sealed = seal_all(&mut tx, &key).await?;
+    tx.commit().await?;
+
+    // The plain text of what was just sealed lingers in the full-text index's old segments, in the
+    // pages it used to fill and in the write-ahead log, until they're all rewritten.
+    let _ = query("INSERT INTO jots_fts (jots_fts) VALUES ('optimize')")
+        .execute(pool)
+        .await?;
+    let _ = query("VACUUM").execute(pool).await?;
+    let _ = query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(pool)
+        .await?;
+
+    Ok(sealed)
+}
+
+// Seal the content of every jot and revision that isn't sealed yet with `key`, returning the number
+// of jots that were sealed.
+pub(crate) async fn seal_all(conn: &mut SqliteConnection, key: &Key) -> Result<usize> {
+    let jots: Vec<(Uuid, Vec<u8>)> = query_as("SELECT jot_id, jot_content FROM jots")
+        .fetch_all(&mut *conn)
+        .await?;
+    let mut sealed = 0;
+    for (id, content) in jots.iter().filter(|(_, c)| !is_sealed(c)) {
+        let _ = query("UPDATE jots SET jot_content = ?1 WHERE jot_id = ?2")
+            .bind(seal(key, *id, content)?)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+        sealed += 1;
+    }
+
+    let revisions: Vec<(Uuid, Uuid, Vec<u8>)> =
+        query_as("SELECT revision_id, jot_id, jot_content FROM jot_revisions")
+            .fetch_all(&mut *conn)
+            .await?;
+    for (id, jot_id, content) in revisions.iter().filter(|(_, _, c)| !is_sealed(c)) {
+        let _ = query("UPDATE jot_revisions SET jot_content = ?1 WHERE revision_id = ?2")
+            .bind(seal(key, *jot_id, content)?)
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(sealed)
+}
+
+/// Open the profile's journal with `make_pool` and unlock it if it's encrypted.
+pub async fn open_journal(profile: &Profile) -> Result<SqlitePool> {
+    let conn = make_pool(&profile.db_file).await?;
+    unlock_journal(&conn, profile).await?;
+
+    Ok(conn)
+}
+
+/// Unlock the journal if it's encrypted, with the passphrase from `get_passphrase`.
+pub async fn unlock_journal(conn: &SqlitePool, profile: &Profile) -> Result<()> {
+    if is_encrypted(conn).await? {
+        unlock(conn, &get_passphrase(profile, false)?).await?;
+    }
+
+    Ok(())
+}
+
+/// The passphrase for the profile's journal: the value of `$JOENAL_PASSPHRASE` if it's set, or the
+/// output of the profile's `passphrase_command`, eg, a password manager or agent, or else whatever
+/// is typed at the terminal, twice if `confirm` is true.
+pub fn get_passphrase(profile: &Profile, confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+
+    if let Some(command) = profile.passphrase_command.as_ref() {
+        let output = Command::new("sh").arg("-c").arg(command).output()?;
+        if !output.status.success() {
+            return Err(Error::Config(format!(
+                "passphrase command '{}' failed: {}",
+                command,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let passphrase = String::from_utf8(output.stdout)
+            .map_err(|_| Error::Encoding("passphrase is not valid UTF-8".to_owned()))?;
+        return Ok(passphrase.trim_end_matches(&['\r', '\n'][..]).to_owned());
+    }
+
+    let prompt = |prompt: &str| {
+        rpassword::prompt_password(prompt).map_err(|e| {
+            Error::Locked(format!(
+                "can't ask for the passphrase ({}); set ${} instead",
+                e, PASSPHRASE_VAR
+            ))
+        })
+    };
+    let passphrase = prompt("Passphrase for the journal: ")?;
+    if passphrase.is_empty() {
+        return Err(Error::Invalid("no passphrase given".to_owned()));
+    }
+    if confirm && prompt("Once more: ")? != passphrase {
+        return Err(Error::Invalid("the passphrases don't match".to_owned()));
+    }
+
+    Ok(passphrase)
+}
+
+// The key new content in the journal is sealed with, if it has one: the oldest, so that every
+// device picks the same one after a merge.
+pub(crate) async fn sealing_key(conn: &mut SqliteConnection) -> Result<Option<Key>> {
+    let id: Option<Uuid> =
+        query_scalar("SELECT key_id FROM journal_keys ORDER BY key_date, key_id LIMIT 1")
+            .fetch_optional(conn)
+            .await?;
+
+    match id {
+        Some(id) => match KEYRING.read().expect("keyring lock").get(&id) {
+            Some(key) => Ok(Some(key.clone())),
+            None => Err(Error::Locked(
+                "the journal is encrypted; unlock it first".to_owned(),
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn is_sealed(content: &[u8]) -> bool {
+    content.starts_with(SEAL_MAGIC)
+}
+
+// Seal the content of the given jot. The nonce is derived from the key, jot and content, so sealing
+// the same content twice gives the same bytes, and the jot ID is authenticated along with it so
+// that sealed content can't be moved to another jot.
+pub(crate) fn seal(key: &Key, jot_id: Uuid, content: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key.nonce_key)
+        .map_err(|e| Error::Encoding(e.to_string()))?;
+    mac.update(b"nonce");
+    mac.update(jot_id.as_bytes());
+    mac.update(content);
+    let nonce = mac.finalize().into_bytes();
+    let nonce = &nonce[..NONCE_LEN];
+
+    let cipher = XChaCha20Poly1305::new((&key.cipher_key).into());
+    let payload = Payload {
+        msg: content,
+        aad: jot_id.as_bytes(),
+    };
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(nonce), payload)
+        .map_err(|_| Error::Encoding("could not seal content".to_owned()))?;
+
+    Ok([SEAL_MAGIC, key.id.as_bytes(), nonce, &ciphertext].concat())
+}
+
+// The plain content of the given jot, if `content` is sealed, using the keys unlocked so far.
+pub(crate) fn unseal(jot_id: Uuid, content: &[u8]) -> Result<Option<Vec<u8>>> {
+    if !is_sealed(content) || content.len() < HEADER_LEN {
+        return Ok(None);
+    }
+
+    let key_id = Uuid::from_slice(&content[SEAL_MAGIC.len()..SEAL_MAGIC.len() + 16])?;
+    let key = match KEYRING.read().expect("keyring lock").get(&key_id) {
+        Some(key) => key.clone(),
+        None => {
+            return Err(Error::Locked(
+                "the journal is encrypted; unlock it first".to_owned(),
+            ))
+        }
+    };
+
+    match open(&key, jot_id, content) {
+        Some(plain) => Ok(Some(plain)),
+        None => Err(Error::Encoding(format!(
+            "sealed content of jot {} has been tampered with",
+            jot_id.to_simple()
+        ))),
+    }
+}
+
+fn open(key: &Key, jot_id: Uuid, content: &[u8]) -> Option<Vec<u8>> {
+    if !is_sealed(content) || content.len() < HEADER_LEN {
+        return None;
+    }
+    let nonce = &content[HEADER_LEN - NONCE_LEN..HEADER_LEN];
+
+    let cipher = XChaCha20Poly1305::new((&key.cipher_key).into());
+    let payload = Payload {
+        msg: &content[HEADER_LEN..],
+        aad: jot_id.as_bytes(),
+    };
+    cipher.decrypt(XNonce::from_slice(nonce), payload).ok()
+}
+
+fn add_key(key: Key) {
+    KEYRING.write().expect("keyring lock").insert(key.id, key);
+}
+
+// Stretch the passphrase into a key with the parameters stored with it, like
+// "argon2id$v=19$m=19456,t=2,p=1", and expand that into the nonce and cipher keys.
+fn derive_key(id: Uuid, passphrase: &str, salt: &[u8], kdf_params: &str) -> Result<Key> {
+    let bad_params = || Error::Encoding(format!("unknown key parameters '{}'", kdf_params));
+
+    let costs = kdf_params
+        .strip_prefix("argon2id$v=19$")
+        .ok_or_else(bad_params)?;
+    let (mut m, mut t, mut p) = (None, None, None);
+    for cost in costs.split(',') {
+        let mut parts = cost.splitn(2, '=');
+        let name = parts.next();
+        let value = parts.next().and_then(|v| v.parse().ok());
+        match name {
+            Some("m") => m = value,
+            Some("t") => t = value,
+            Some("p") => p = value,
+            _ => return Err(bad_params()),
+        }
+    }
+    let (m, t, p) = match (m, t, p) {
+        (Some(m), Some(t), Some(p)) => (m, t, p),
+        _ => return Err(bad_params()),
+    };
+
+    let params = Params::new(m, t, p, Some(32)).map_err(|e| Error::Encoding(e.to_string()))?;
+    let mut master = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut master)
+        .map_err(|e| Error::Encoding(e.to_string()))?;
+
+    let hkdf = Hkdf::<Sha256>::new(Some(id.as_bytes()), &master);
+    let mut key = Key {
+        id,
+        nonce_key: [0u8; 32],
+        cipher_key: [0u8; 32],
+    };
+    hkdf.expand(b"joenal nonce", &mut key.nonce_key)
+        .and_then(|_| hkdf.expand(b"joenal cipher", &mut key.cipher_key))
+        .map_err(|e| Error::Encoding(e.to_string()))?;
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cheap parameters, so the tests don't spend their time stretching passphrases
+    const TEST_PARAMS: &str = "argon2id$v=19$m=64,t=1,p=1";
+
+    fn key(passphrase: &str) -> Key {
+        derive_key(Uuid::new_v4(), passphrase, b"sixteen byte slt", TEST_PARAMS).unwrap()
+    }
+
+    #[test]
+    fn derive_key_is_repeatable() {
+        let id = Uuid::new_v4();
+        let derive = |passphrase, salt: &[u8]| derive_key(id, passphrase, salt, TEST_PARAMS);
+
+        let a = derive("correct horse", b"sixteen byte slt").unwrap();
+        let b = derive("correct horse", b"sixteen byte slt").unwrap();
+        assert_eq!(a.cipher_key, b.cipher_key);
+        assert_eq!(a.nonce_key, b.nonce_key);
+        assert_ne!(a.cipher_key, a.nonce_key);
+
+        let c = derive("battery staple", b"sixteen byte slt").unwrap();
+        let d = derive("correct horse", b"another salt!!!!").unwrap();
+        assert_ne!(a.cipher_key, c.cipher_key);
+        assert_ne!(a.cipher_key, d.cipher_key);
+    }
+
+    #[test]
+    fn derive_key_checks_params() {
+        for params in &[
+            "",
+            "argon2i$v=19$m=64,t=1,p=1",
+            "argon2id$v=19$m=64,t=1",
+            "argon2id$v=19$m=64,t=1,p=1,x=2",
+            "argon2id$v=19$m=lots,t=1,p=1",
+        ] {
+            let derived = derive_key(Uuid::new_v4(), "pass", b"sixteen byte slt", params);
+            assert!(derived.is_err(), "{}", params);
+        }
+    }
+
+    #[test]
+    fn seal_round_trip() {
+        let key = key("correct horse");
+        let jot = Uuid::new_v4();
+        let sealed = seal(&key, jot, b"dear diary").unwrap();
+
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.windows(5).any(|w| w == b"diary"));
+        // the same content seals the same way, so merges see it as unchanged
+        assert_eq!(seal(&key, jot, b"dear diary").unwrap(), sealed);
+        assert_ne!(seal(&key, Uuid::new_v4(), b"dear diary").unwrap(), sealed);
+
+        assert_eq!(
+            open(&key, jot, &sealed).as_deref(),
+            Some(&b"dear diary"[..])
+        );
+        add_key(key);
+        assert_eq!(
+            unseal(jot, &sealed).unwrap().as_deref(),
+            Some(&b"dear diary"[..])
+        );
+    }
+
+    #[test]
+    fn plain_content_isnt_unsealed() {
+        assert_eq!(unseal(Uuid::new_v4(), b"dear diary").unwrap(), None);
+        assert_eq!(unseal(Uuid::new_v4(), SEAL_MAGIC).unwrap(), None);
+    }
+
+    #[test]
+    fn tampering_is_caught() {
+        let key = key("correct horse");
+        let jot = Uuid::new_v4();
+        let sealed = seal(&key, jot, b"dear diary").unwrap();
+        add_key(key);
+
+        let mut flipped = sealed.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert!(matches!(unseal(jot, &flipped), Err(Error::Encoding(_))));
+
+        let mut nonce = sealed.clone();
+        nonce[HEADER_LEN - 1] ^= 1;
+        assert!(matches!(unseal(jot, &nonce), Err(Error::Encoding(_))));
+
+        // sealed content can't be moved to another jot
+        assert!(matches!(
+            unseal(Uuid::new_v4(), &sealed),
+            Err(Error::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn wrong_or_missing_key() {
+        let jot = Uuid::new_v4();
+        let sealed = seal(&key("correct horse"), jot, b"dear diary").unwrap();
+        assert!(matches!(unseal(jot, &sealed), Err(Error::Locked(_))));
+
+        let mut other = key("battery staple");
+        other.id = Uuid::from_slice(&sealed[SEAL_MAGIC.len()..SEAL_MAGIC.len() + 16]).unwrap();
+        assert_eq!(open(&other, jot, &sealed), None);
+    }
+}
@@ -0,0 +1,250 @@
+use chrono::{prelude::*, Duration};
+
+use super::StarDate;
+
+/// The stretch of time named by a date expression. "yesterday" covers all of yesterday, while an
+/// exact time like "3 hours ago" or an RFC3339 timestamp starts and ends at the same instant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DateSpan {
+    pub start: StarDate,
+    pub end: StarDate,
+}
+
+/// Understand a date given as RFC3339, as `YYYY-MM-DD[ HH:MM[:SS]]` in local time, or as one of a
+/// few English phrases relative to now: "today", "yesterday", "monday", "last friday", "this week",
+/// "last month", "3 weeks ago", "an hour ago", and so on. Weeks start on Monday.
+pub fn parse_date_span(text: &str) -> Option<DateSpan> {
+    parse_date_span_at(text, Local::now())
+}
+
+fn parse_date_span_at(text: &str, now: DateTime<Local>) -> Option<DateSpan> {
+    let text = text.trim();
+
+    if let Ok(d) = DateTime::parse_from_rfc3339(text) {
+        return Some(instant(d.with_timezone(&Utc)));
+    }
+
+    for fmt in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"].iter() {
+        if let Ok(d) = NaiveDateTime::parse_from_str(text, fmt) {
+            return Some(instant(local_to_utc(d)?));
+        }
+    }
+
+    if let Ok(d) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return days(d, 1);
+    }
+
+    let text = text.to_lowercase();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let today = now.naive_local().date();
+
+    match words.as_slice() {
+        ["now"] => Some(instant(now.with_timezone(&Utc))),
+        ["today"] => days(today, 1),
+        ["yesterday"] => days(today.pred_opt()?, 1),
+        ["tomorrow"] => days(today.succ_opt()?, 1),
+        ["this", unit] => period(today, unit, 0),
+        ["last", unit] | ["previous", unit] => match weekday(unit) {
+            Some(wd) => days(last_weekday(today, wd), 1),
+            None => period(today, unit, 1),
+        },
+        [day] => days(last_weekday(today, weekday(day)?), 1),
+        [count, unit, "ago"] => {
+            let count = match *count {
+                "a" | "an" | "one" => 1,
+                n => n.parse().ok()?,
+            };
+            Some(instant(ago(now, count, unit)?.with_timezone(&Utc)))
+        }
+        _ => None,
+    }
+}
+
+fn instant(d: StarDate) -> DateSpan {
+    DateSpan { start: d, end: d }
+}
+
+fn local_to_utc(d: NaiveDateTime) -> Option<StarDate> {
+    Local
+        .from_local_datetime(&d)
+        .earliest()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+// the span covering `n` whole local days starting with `first`
+fn days(first: NaiveDate, n: i64) -> Option<DateSpan> {
+    let start = local_to_utc(first.and_hms_opt(0, 0, 0)?)?;
+    let end = local_to_utc((first + Duration::days(n)).and_hms_opt(0, 0, 0)?)?;
+    Some(DateSpan { start, end })
+}
+
+// the week, month or year containing `today`, or the one `back` periods before it
+fn period(today: NaiveDate, unit: &str, back: i32) -> Option<DateSpan> {
+    match unit {
+        "week" => {
+            let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+            days(monday - Duration::weeks(back as i64), 7)
+        }
+        "month" => {
+            let first = add_months(today.with_day(1)?, -back)?;
+            let next = add_months(first, 1)?;
+            days(first, (next - first).num_days())
+        }
+        "year" => {
+            let first = NaiveDate::from_ymd_opt(today.year() - back, 1, 1)?;
+            let next = NaiveDate::from_ymd_opt(today.year() - back + 1, 1, 1)?;
+            days(first, (next - first).num_days())
+        }
+        _ => None,
+    }
+}
+
+fn ago(now: DateTime<Local>, count: i64, unit: &str) -> Option<DateTime<Local>> {
+    let unit = unit.strip_suffix('s').unwrap_or(unit);
+    match unit {
+        "second" | "sec" => Some(now - Duration::seconds(count)),
+        "minute" | "min" => Some(now - Duration::minutes(count)),
+        "hour" => Some(now - Duration::hours(count)),
+        "day" => Some(now - Duration::days(count)),
+        "week" => Some(now - Duration::weeks(count)),
+        "month" | "year" => {
+            let months = if unit == "year" { count * 12 } else { count };
+            let date = add_months(now.naive_local().date(), -(months as i32))?;
+            Local
+                .from_local_datetime(&date.and_time(now.time()))
+                .earliest()
+        }
+        _ => None,
+    }
+}
+
+// move by whole months, clamping the day to the end of a shorter month
+fn add_months(date: NaiveDate, months: i32) -> Option<NaiveDate> {
+    let total = date.year() * 12 + date.month0() as i32 + months;
+    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+// the most recent `wd` before today
+fn last_weekday(today: NaiveDate, wd: Weekday) -> NaiveDate {
+    let back = (today.weekday().num_days_from_monday() + 7 - wd.num_days_from_monday()) % 7;
+    let back = if back == 0 { 7 } else { back };
+    today - Duration::days(back as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .and_then(|d| d.and_hms_opt(h, min, 0))
+            .unwrap()
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.from_local_datetime(&naive(y, m, d, h, min)).unwrap()
+    }
+
+    // Wednesday, 2021-05-12 15:30 local time
+    fn now() -> DateTime<Local> {
+        at(2021, 5, 12, 15, 30)
+    }
+
+    fn local(y: i32, m: u32, d: u32) -> StarDate {
+        at(y, m, d, 0, 0).with_timezone(&Utc)
+    }
+
+    fn span(text: &str) -> DateSpan {
+        parse_date_span_at(text, now()).unwrap()
+    }
+
+    #[test]
+    fn exact_times_are_instants() {
+        let d = Utc.from_utc_datetime(&naive(2021, 5, 4, 13, 30));
+        assert_eq!(span("2021-05-04T13:30:00Z"), instant(d));
+        assert_eq!(span("now"), instant(now().with_timezone(&Utc)));
+
+        let d = at(2021, 5, 4, 15, 30).with_timezone(&Utc);
+        assert_eq!(span("2021-05-04 15:30"), instant(d));
+        assert_eq!(span("2021-05-04 15:30:00"), instant(d));
+        assert_eq!(span(" 2021-05-04T15:30:00 "), instant(d));
+    }
+
+    #[test]
+    fn days_cover_the_whole_day() {
+        let whole = |y, m, d: u32| DateSpan {
+            start: local(y, m, d),
+            end: local(y, m, d + 1),
+        };
+        assert_eq!(span("2021-05-04"), whole(2021, 5, 4));
+        assert_eq!(span("today"), whole(2021, 5, 12));
+        assert_eq!(span("Yesterday"), whole(2021, 5, 11));
+        assert_eq!(span("tomorrow"), whole(2021, 5, 13));
+        assert_eq!(span("monday"), whole(2021, 5, 10));
+        assert_eq!(span("last fri"), whole(2021, 5, 7));
+        // the same weekday as today means a week ago, not today
+        assert_eq!(span("wednesday"), whole(2021, 5, 5));
+    }
+
+    #[test]
+    fn periods() {
+        let between = |start, end| DateSpan { start, end };
+        assert_eq!(
+            span("this week"),
+            between(local(2021, 5, 10), local(2021, 5, 17))
+        );
+        assert_eq!(
+            span("last week"),
+            between(local(2021, 5, 3), local(2021, 5, 10))
+        );
+        assert_eq!(
+            span("last month"),
+            between(local(2021, 4, 1), local(2021, 5, 1))
+        );
+        assert_eq!(
+            span("previous year"),
+            between(local(2020, 1, 1), local(2021, 1, 1))
+        );
+    }
+
+    #[test]
+    fn ago() {
+        let back = |d: DateTime<Local>| instant(d.with_timezone(&Utc));
+        assert_eq!(span("an hour ago"), back(now() - Duration::hours(1)));
+        assert_eq!(span("3 days ago"), back(now() - Duration::days(3)));
+        assert_eq!(span("2 weeks ago"), back(now() - Duration::weeks(2)));
+        assert_eq!(span("1 month ago"), back(at(2021, 4, 12, 15, 30)));
+    }
+
+    #[test]
+    fn months_clamp_to_shorter_ones() {
+        let d = NaiveDate::from_ymd_opt;
+        assert_eq!(add_months(d(2021, 3, 31).unwrap(), -1), d(2021, 2, 28));
+        assert_eq!(add_months(d(2020, 3, 31).unwrap(), -1), d(2020, 2, 29));
+        assert_eq!(add_months(d(2021, 1, 15).unwrap(), -1), d(2020, 12, 15));
+        assert_eq!(add_months(d(2021, 12, 1).unwrap(), 1), d(2022, 1, 1));
+    }
+
+    #[test]
+    fn nonsense() {
+        for text in &["", "soon", "last fortnight", "x days ago", "2021-13-01"] {
+            assert_eq!(parse_date_span_at(text, now()), None, "{:?}", text);
+        }
+    }
+}
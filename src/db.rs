@@ -1,11 +1,293 @@
-use std::env;
+use std::str::FromStr;
 
-use sqlx::SqlitePool;
+use chrono::Utc;
+use sqlx::{
+    query, query_as, query_scalar,
+    sqlite::{SqliteConnectOptions, SqlitePool},
+    Executor,
+};
 
-pub async fn make_pool() -> SqlitePool {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+use super::{Error, Result, StarDate};
 
-    let conn = SqlitePool::connect(&database_url).await.unwrap();
+/// A schema change that ships inside the binary. The SQL lives in the `migrations` directory at
+/// the root of the crate and gets pulled in with `include_str!`.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    up: &'static str,
+    down: &'static str,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initialize",
+        up: include_str!("../migrations/2019-04-23-045358_initialize/up.sql"),
+        down: include_str!("../migrations/2019-04-23-045358_initialize/down.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "jot_links",
+        up: include_str!("../migrations/2026-10-18-120000_jot_links/up.sql"),
+        down: include_str!("../migrations/2026-10-18-120000_jot_links/down.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "jots_fts",
+        up: include_str!("../migrations/2026-10-18-130000_jots_fts/up.sql"),
+        down: include_str!("../migrations/2026-10-18-130000_jots_fts/down.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "jot_revisions",
+        up: include_str!("../migrations/2026-10-18-140000_jot_revisions/up.sql"),
+        down: include_str!("../migrations/2026-10-18-140000_jot_revisions/down.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "tombstones",
+        up: include_str!("../migrations/2026-10-18-150000_tombstones/up.sql"),
+        down: include_str!("../migrations/2026-10-18-150000_tombstones/down.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "tag_aliases",
+        up: include_str!("../migrations/2026-10-18-160000_tag_aliases/up.sql"),
+        down: include_str!("../migrations/2026-10-18-160000_tag_aliases/down.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "tag_namespaces",
+        up: include_str!("../migrations/2026-10-18-170000_tag_namespaces/up.sql"),
+        down: include_str!("../migrations/2026-10-18-170000_tag_namespaces/down.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "journal_keys",
+        up: include_str!("../migrations/2026-10-18-180000_journal_keys/up.sql"),
+        down: include_str!("../migrations/2026-10-18-180000_journal_keys/down.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "author_ids",
+        up: include_str!("../migrations/2026-10-18-190000_author_ids/up.sql"),
+        down: include_str!("../migrations/2026-10-18-190000_author_ids/down.sql"),
+    },
+];
+
+/// The newest schema version this build of joenal knows how to use.
+pub fn latest_schema_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn migrations() -> &'static [Migration] {
+    MIGRATIONS
+}
+
+/// Open the database at the given path or `sqlite:` URL, creating it if need be, and bring its
+/// schema up to date.
+pub async fn make_pool(database_url: &str) -> Result<SqlitePool> {
+    let conn = open_pool(database_url).await?;
+
+    let version = schema_version(&conn).await?;
+    let latest = latest_schema_version();
+    if version > latest {
+        return Err(Error::Invalid(format!(
+            "database schema is at version {}, but this joenal only understands up to version {}",
+            version, latest
+        )));
+    }
+
+    migrate_up(&conn).await?;
+
+    Ok(conn)
+}
+
+/// Open the database without touching its schema; used by `joenal-admin migrate`. Deleted content
+/// is overwritten rather than left in free pages, which matters most once a journal is encrypted
+/// and its plain text has been replaced.
+pub async fn open_pool(database_url: &str) -> Result<SqlitePool> {
+    let opts = SqliteConnectOptions::from_str(database_url)?
+        .create_if_missing(true)
+        .foreign_keys(true)
+        .pragma("secure_delete", "ON");
+
+    let conn = SqlitePool::connect_with(opts).await?;
+
+    Ok(conn)
+}
+
+/// The highest migration version that has been applied to the database; zero for an empty one.
+pub async fn schema_version(pool: &SqlitePool) -> Result<i64> {
+    ensure_version_table(pool).await?;
+
+    let version: Option<i64> = query_scalar("SELECT MAX(version) FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(version.unwrap_or(0))
+}
+
+/// All the migrations that have been recorded as applied, oldest first.
+pub async fn applied_migrations(pool: &SqlitePool) -> Result<Vec<(i64, String, StarDate)>> {
+    ensure_version_table(pool).await?;
+
+    let applied = query_as(
+        r#"
+SELECT version, name, applied_date FROM schema_version ORDER BY version
+"#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(applied)
+}
+
+/// Apply every bundled migration newer than the database's current version, returning the
+/// versions that were applied.
+pub async fn migrate_up(pool: &SqlitePool) -> Result<Vec<i64>> {
+    let current = schema_version(pool).await?;
+    let mut applied = Vec::new();
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut tx = pool.begin().await?;
+        tx.execute(migration.up).await?;
+        let _ =
+            query("INSERT INTO schema_version (version, name, applied_date) VALUES (?1, ?2, ?3)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .bind(Utc::now())
+                .execute(&mut tx)
+                .await?;
+        tx.commit().await?;
+
+        applied.push(migration.version);
+    }
+
+    Ok(applied)
+}
+
+/// Revert the most recently applied migration, returning its version.
+pub async fn migrate_down(pool: &SqlitePool) -> Result<Option<i64>> {
+    let current = schema_version(pool).await?;
+    if current == 0 {
+        return Ok(None);
+    }
+
+    let migration = match MIGRATIONS.iter().find(|m| m.version == current) {
+        Some(m) => m,
+        None => {
+            return Err(Error::Invalid(format!(
+                "don't know how to revert schema version {}; is this joenal too old?",
+                current
+            )))
+        }
+    };
+
+    let mut tx = pool.begin().await?;
+    // without its key record, sealed content can never be opened again
+    if migration.name == "journal_keys" {
+        let keys: i64 = query_scalar("SELECT COUNT(*) FROM journal_keys")
+            .fetch_one(&mut tx)
+            .await?;
+        if keys > 0 {
+            return Err(Error::Invalid(format!(
+                "the journal is encrypted; reverting schema version {} would lose its key for good",
+                migration.version
+            )));
+        }
+    }
+    tx.execute(migration.down).await?;
+    let _ = query("DELETE FROM schema_version WHERE version = ?1")
+        .bind(migration.version)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Some(current))
+}
+
+// Databases created before the migrations were bundled had the initial schema applied by hand, so
+// if we find a `jots` table without any version records, count the first migration as applied.
+async fn ensure_version_table(pool: &SqlitePool) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    tx.execute(
+        r#"
+CREATE TABLE IF NOT EXISTS schema_version (
+       version INTEGER NOT NULL PRIMARY KEY,
+       name TEXT NOT NULL,
+       applied_date TEXT NOT NULL
+);
+"#,
+    )
+    .await?;
+
+    let versions: i64 = query_scalar("SELECT COUNT(*) FROM schema_version")
+        .fetch_one(&mut tx)
+        .await?;
+    let jots_table: i64 =
+        query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'jots'")
+            .fetch_one(&mut tx)
+            .await?;
+
+    if versions == 0 && jots_table > 0 {
+        let first = &MIGRATIONS[0];
+        let _ =
+            query("INSERT INTO schema_version (version, name, applied_date) VALUES (?1, ?2, ?3)")
+                .bind(first.version)
+                .bind(first.name)
+                .bind(Utc::now())
+                .execute(&mut tx)
+                .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::create_temp_file;
+
+    #[async_std::test]
+    async fn migrate_down_keeps_the_key_of_an_encrypted_journal() {
+        let (path, file) = create_temp_file(".db").unwrap();
+        drop(file);
+        let pool = make_pool(&path.to_string_lossy()).await.unwrap();
+
+        let _ = query(
+            "INSERT INTO journal_keys (key_id, salt, kdf_params, key_check, device_id, key_date)
+             VALUES (?1, x'00', 'argon2id$v=19$m=64,t=1,p=1', x'00', ?1, ?2)",
+        )
+        .bind(uuid::Uuid::new_v4())
+        .bind(Utc::now())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        while schema_version(&pool).await.unwrap() > 8 {
+            migrate_down(&pool).await.unwrap();
+        }
+        assert!(matches!(migrate_down(&pool).await, Err(Error::Invalid(_))));
+        assert_eq!(schema_version(&pool).await.unwrap(), 8);
+        let keys: i64 = query_scalar("SELECT COUNT(*) FROM journal_keys")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(keys, 1);
+
+        let _ = query("DELETE FROM journal_keys")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(migrate_down(&pool).await.unwrap(), Some(8));
 
-    conn
+        pool.close().await;
+        let _ = fs::remove_file(path);
+    }
 }
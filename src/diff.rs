@@ -0,0 +1,107 @@
+use std::fmt::Display;
+
+/// One line of a line-by-line comparison between two texts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiffLine<'t> {
+    Same(&'t str),
+    Removed(&'t str),
+    Added(&'t str),
+}
+
+impl Display for DiffLine<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DiffLine::Same(l) => write!(f, "  {}", l),
+            DiffLine::Removed(l) => write!(f, "- {}", l),
+            DiffLine::Added(l) => write!(f, "+ {}", l),
+        }
+    }
+}
+
+/// Compare two texts line by line, keeping the longest run of lines they have in common.
+pub fn diff_lines<'t>(old: &'t str, new: &'t str) -> Vec<DiffLine<'t>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let (n, m) = (old.len(), new.len());
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old[i..].iter().map(|l| DiffLine::Removed(l)));
+    diff.extend(new[j..].iter().map(|l| DiffLine::Added(l)));
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_lines, DiffLine::*};
+
+    #[test]
+    fn identical() {
+        assert_eq!(diff_lines("a\nb", "a\nb"), vec![Same("a"), Same("b")]);
+        assert_eq!(diff_lines("", ""), vec![]);
+    }
+
+    #[test]
+    fn added_and_removed() {
+        assert_eq!(diff_lines("", "a\nb"), vec![Added("a"), Added("b")]);
+        assert_eq!(diff_lines("a\nb", ""), vec![Removed("a"), Removed("b")]);
+        assert_eq!(
+            diff_lines("a\nc", "a\nb\nc"),
+            vec![Same("a"), Added("b"), Same("c")]
+        );
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nc"),
+            vec![Same("a"), Removed("b"), Same("c")]
+        );
+    }
+
+    #[test]
+    fn changed_lines_are_removed_then_added() {
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nB\nc\nd"),
+            vec![Same("a"), Removed("b"), Added("B"), Same("c"), Added("d")]
+        );
+    }
+
+    #[test]
+    fn keeps_the_longest_common_run() {
+        let diff = diff_lines("x\na\nb\nc", "a\nb\nc\nx");
+        let same: Vec<_> = diff.iter().filter(|l| matches!(l, Same(_))).collect();
+        assert_eq!(same, vec![&Same("a"), &Same("b"), &Same("c")]);
+    }
+
+    #[test]
+    fn display() {
+        let shown: Vec<String> = diff_lines("a\nb", "a\nc")
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(shown, vec!["  a", "- b", "+ c"]);
+    }
+}
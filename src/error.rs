@@ -0,0 +1,99 @@
+use std::{fmt, io};
+
+/// Everything that can go wrong in the library.
+#[derive(Debug)]
+pub enum Error {
+    /// There's no jot, tag or file with the given name or ID.
+    NotFound(String),
+    /// The database couldn't be opened, read or written.
+    Database(sqlx::Error),
+    /// The configuration couldn't be loaded, or is missing something.
+    Config(String),
+    /// Something that should have been a UUID wasn't.
+    InvalidId(uuid::Error),
+    /// Data couldn't be read or written in its expected format, eg, JSON, base64 or a date.
+    Encoding(String),
+    /// A file couldn't be read or written.
+    Io(io::Error),
+    /// Content is sealed with a key that hasn't been unlocked, or the passphrase was wrong.
+    Locked(String),
+    /// The request doesn't make sense for the journal as it stands, eg, editing a deleted jot or
+    /// renaming a tag to one that already exists.
+    Invalid(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound(what) => write!(f, "{}", what),
+            Error::Database(e) => write!(f, "database error: {}", e),
+            Error::Config(e) => write!(f, "configuration error: {}", e),
+            Error::InvalidId(e) => write!(f, "invalid ID: {}", e),
+            Error::Encoding(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Locked(e) => write!(f, "{}", e),
+            Error::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Database(e) => Some(e),
+            Error::InvalidId(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        Error::Database(e)
+    }
+}
+
+impl From<uuid::Error> for Error {
+    fn from(e: uuid::Error) -> Self {
+        Error::InvalidId(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<confy::ConfyError> for Error {
+    fn from(e: confy::ConfyError) -> Self {
+        Error::Config(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Encoding(e.to_string())
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(e: base64::DecodeError) -> Self {
+        Error::Encoding(e.to_string())
+    }
+}
+
+impl From<chrono::ParseError> for Error {
+    fn from(e: chrono::ParseError) -> Self {
+        Error::Encoding(e.to_string())
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(e: fmt::Error) -> Self {
+        Error::Encoding(e.to_string())
+    }
+}
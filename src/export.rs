@@ -0,0 +1,254 @@
+use std::{collections::HashMap, fmt::Write as _, fs, io, path::Path};
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, sqlite::SqliteRow, FromRow, SqlitePool};
+
+use super::{
+    get_jot_tags, get_jots_filtered, Jot, JotFilter, JournalKey, Link, Mapping, Result, Revision,
+    Tag, TagAlias, Tombstone, Uuid,
+};
+
+/// One line of an NDJSON export: a row from one of the tables, tagged with its kind, eg,
+/// `{"type":"tag","tag_id":...}`. Content is base64, and stays sealed if the journal is encrypted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Record {
+    Tag(Tag),
+    Jot(Jot),
+    Mapping(Mapping),
+    TagAlias(TagAlias),
+    Link(Link),
+    Revision(Revision),
+    Tombstone(Tombstone),
+    JournalKey(JournalKey),
+}
+
+/// Write every jot to its own Markdown file under `dir`, in `YYYY/MM` subdirectories by local
+/// creation date. Each file starts with YAML front matter holding everything needed to recreate
+/// the jot exactly, including its ID, tags and links; the rest of the file is the jot's text,
+/// byte for byte. Jots that aren't text are written to a file of their own next to the Markdown
+/// file, which names it in its `attachment` field. Returns the number of jots written.
+pub async fn export_markdown(conn: &SqlitePool, dir: &Path) -> Result<usize> {
+    let jots = get_jots_filtered(conn, &JotFilter::default()).await?;
+    let tags = get_jot_tags(conn).await?;
+    let links = get_jot_links(conn).await?;
+
+    for jot in jots.iter() {
+        let (subdir, stem) = export_name(jot);
+        let subdir = dir.join(subdir);
+        fs::create_dir_all(&subdir)?;
+
+        let content = jot.content();
+        let mut doc = String::from("---\n");
+        writeln!(doc, "jot_id: {}", jot.id())?;
+        match jot.created() {
+            Some(date) => writeln!(doc, "creation_date: {}", yaml_str(&date.to_rfc3339()))?,
+            None => writeln!(doc, "creation_date: null")?,
+        }
+        writeln!(doc, "device_id: {}", jot.device())?;
+        match jot.dup() {
+            Some(dup) => writeln!(doc, "dup_id: {}", dup)?,
+            None => writeln!(doc, "dup_id: null")?,
+        }
+        match jot.author() {
+            Some(author) => writeln!(doc, "author_id: {}", author)?,
+            None => writeln!(doc, "author_id: null")?,
+        }
+        writeln!(doc, "content_type: {}", yaml_str(content.mime_type))?;
+        write_list(&mut doc, "tags", tags.get(&jot.id()))?;
+        write_list(&mut doc, "links", links.get(&jot.id()))?;
+
+        let body = match content.text() {
+            Some(text) => text,
+            None => {
+                let file = format!("{}.{}", stem, attachment_extension(content.mime_type));
+                fs::write(subdir.join(&file), content.bytes)?;
+                writeln!(doc, "attachment: {}", yaml_str(&file))?;
+                ""
+            }
+        };
+        doc.push_str("---\n");
+        doc.push_str(body);
+
+        fs::write(subdir.join(format!("{}.md", stem)), doc)?;
+    }
+
+    Ok(jots.len())
+}
+
+/// Write every row of the database to `out` as newline-delimited JSON, one `Record` per line, in an
+/// order that lets them be inserted one by one: tags and jots before the rows that refer to them.
+/// Rows are sorted by ID within each table, so exporting an unchanged database gives the same
+/// output. Returns the number of records written.
+pub async fn export_ndjson(conn: &SqlitePool, out: &mut impl io::Write) -> Result<usize> {
+    let mut count = 0;
+
+    count += export_rows(conn, out, "SELECT * FROM tags ORDER BY tag_id", Record::Tag).await?;
+    count += export_rows(conn, out, "SELECT * FROM jots ORDER BY jot_id", Record::Jot).await?;
+    count += export_rows(
+        conn,
+        out,
+        "SELECT * FROM tag_map ORDER BY mapping_id",
+        Record::Mapping,
+    )
+    .await?;
+    count += export_rows(
+        conn,
+        out,
+        "SELECT * FROM tag_aliases ORDER BY alias_id",
+        Record::TagAlias,
+    )
+    .await?;
+    count += export_rows(
+        conn,
+        out,
+        "SELECT * FROM jot_links ORDER BY link_id",
+        Record::Link,
+    )
+    .await?;
+    count += export_rows(
+        conn,
+        out,
+        "SELECT * FROM jot_revisions ORDER BY revision_id",
+        Record::Revision,
+    )
+    .await?;
+    count += export_rows(
+        conn,
+        out,
+        "SELECT * FROM tombstones ORDER BY jot_id",
+        Record::Tombstone,
+    )
+    .await?;
+
+    count += export_rows(
+        conn,
+        out,
+        "SELECT * FROM journal_keys ORDER BY key_id",
+        Record::JournalKey,
+    )
+    .await?;
+
+    out.flush()?;
+
+    Ok(count)
+}
+
+async fn export_rows<T>(
+    conn: &SqlitePool,
+    out: &mut impl io::Write,
+    sql: &str,
+    record: fn(T) -> Record,
+) -> Result<usize>
+where
+    T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+{
+    let rows: Vec<T> = query_as(sql).fetch_all(conn).await?;
+    let count = rows.len();
+    for row in rows {
+        serde_json::to_writer(&mut *out, &record(row))?;
+        out.write_all(b"\n")?;
+    }
+
+    Ok(count)
+}
+
+// The links leading out of each jot as "<type> <to jot ID>", by jot ID.
+async fn get_jot_links(conn: &SqlitePool) -> Result<HashMap<Uuid, Vec<String>>> {
+    let rows: Vec<Link> = query_as("SELECT * FROM jot_links ORDER BY link_date")
+        .fetch_all(conn)
+        .await?;
+
+    let mut links: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for link in rows {
+        links.entry(link.from_jot()).or_default().push(format!(
+            "{} {}",
+            link.link_type(),
+            link.to_jot()
+        ));
+    }
+
+    Ok(links)
+}
+
+// Where a jot's file goes, eg, ("2021/05", "2021-05-04-153000-0a1b2c3d").
+fn export_name(jot: &Jot) -> (String, String) {
+    let id = jot.id().to_simple().to_string();
+    match jot.created() {
+        Some(date) => {
+            let date = date.with_timezone(&Local);
+            (
+                date.format("%Y/%m").to_string(),
+                format!("{}-{}", date.format("%Y-%m-%d-%H%M%S"), &id[..8]),
+            )
+        }
+        None => ("undated".to_owned(), id),
+    }
+}
+
+fn attachment_extension(mime_type: &str) -> String {
+    let essence = mime_type.split(';').next().unwrap_or_default().trim();
+    mime_guess::get_mime_extensions_str(essence)
+        .and_then(|exts| exts.first())
+        .map(|ext| ext.to_string())
+        .unwrap_or_else(|| "bin".to_owned())
+}
+
+fn write_list(doc: &mut String, key: &str, items: Option<&Vec<String>>) -> std::fmt::Result {
+    match items {
+        Some(items) if !items.is_empty() => {
+            writeln!(doc, "{}:", key)?;
+            for item in items.iter() {
+                writeln!(doc, "  - {}", yaml_str(item))?;
+            }
+            Ok(())
+        }
+        _ => writeln!(doc, "{}: []", key),
+    }
+}
+
+// A double-quoted YAML string, which can hold anything once backslashes, quotes and control
+// characters are escaped.
+fn yaml_str(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::yaml_str;
+
+    #[test]
+    fn plain_text_is_just_quoted() {
+        assert_eq!(yaml_str("work"), r#""work""#);
+        assert_eq!(yaml_str(""), r#""""#);
+        assert_eq!(
+            yaml_str("it's: [fine], #really"),
+            r#""it's: [fine], #really""#
+        );
+        assert_eq!(yaml_str("naïve ☕"), "\"naïve ☕\"");
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(yaml_str(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(yaml_str(r"C:\temp"), r#""C:\\temp""#);
+        assert_eq!(yaml_str("a\nb\tc"), r#""a\nb\tc""#);
+        assert_eq!(yaml_str("bell\u{7}\r"), r#""bell\u0007\u000d""#);
+    }
+}
@@ -0,0 +1,303 @@
+use regex::Regex;
+use sqlx::{sqlite::SqliteArguments, Arguments};
+
+use super::{split_tag, DateSpan, StarDate, Tag, Uuid};
+
+/// Everything `get_jots_filtered` can narrow the jots down by; the default selects every jot.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JotFilter {
+    pub tags: TagFilter,
+    /// Only jots created at or after this time.
+    pub since: Option<StarDate>,
+    /// Only jots created up to the end of this span: before the end of a span like "yesterday", or
+    /// at or before an exact time.
+    pub until: Option<DateSpan>,
+}
+
+impl JotFilter {
+    // `known` must hold every tag if the tag filter has patterns in it; see `TagFilter`.
+    pub(crate) fn push_conditions(
+        &self,
+        conds: &mut Vec<String>,
+        args: &mut SqliteArguments,
+        known: &[Tag],
+    ) {
+        self.tags.push_conditions(conds, args, known);
+
+        // compare through julianday() so that dates written in other formats still sort properly
+        if let Some(since) = self.since {
+            conds.push("julianday(jots.jot_creation_date) >= julianday(?)".to_owned());
+            args.add(since);
+        }
+        if let Some(until) = self.until {
+            if until.start == until.end {
+                conds.push("julianday(jots.jot_creation_date) <= julianday(?)".to_owned());
+            } else {
+                conds.push("julianday(jots.jot_creation_date) < julianday(?)".to_owned());
+            }
+            args.add(until.end);
+        }
+    }
+}
+
+/// A position in the newest-first order of jots, given by the last jot of one page and passed in to
+/// get the next; see `Jot::cursor`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JotCursor {
+    pub date: Option<StarDate>,
+    pub id: Uuid,
+}
+
+impl JotCursor {
+    // Add the SQL condition for the jots after this cursor, newest first with ties broken by ID;
+    // jots without a date come last.
+    pub(crate) fn push_conditions(&self, conds: &mut Vec<String>, args: &mut SqliteArguments) {
+        match self.date {
+            Some(date) => {
+                conds.push(
+                    "(jots.jot_creation_date < ? OR jots.jot_creation_date IS NULL \
+                     OR (jots.jot_creation_date = ? AND jots.jot_id < ?))"
+                        .to_owned(),
+                );
+                args.add(date);
+                args.add(date);
+                args.add(self.id);
+            }
+            None => {
+                conds.push("(jots.jot_creation_date IS NULL AND jots.jot_id < ?)".to_owned());
+                args.add(self.id);
+            }
+        }
+    }
+}
+
+/// Which tags a jot must, may, or must not have in order to be selected. An empty filter selects
+/// every jot.
+///
+/// Besides plain tags, each entry may be a pattern: "host:*" stands for every tag in the "host"
+/// namespace, while in other patterns `*` matches anything but a `/`, `**` matches anything at all,
+/// and `?` matches a single character, eg, "pwd:~/src/**". A leading `~` in a namespaced tag's
+/// value is the home directory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TagFilter {
+    /// The jot must have every one of these tags.
+    pub all: Vec<String>,
+    /// The jot must have at least one of these tags, if there are any.
+    pub any: Vec<String>,
+    /// The jot must have none of these tags.
+    pub none: Vec<String>,
+}
+
+impl TagFilter {
+    /// Build a filter from command-line style tag specs: a spec starting with `!` excludes that
+    /// tag, and the rest are either all required or, with `match_any`, alternatives.
+    pub fn parse<'a>(specs: impl IntoIterator<Item = &'a str>, match_any: bool) -> Self {
+        let mut filter = TagFilter::default();
+
+        for spec in specs {
+            let spec = spec.trim();
+            if let Some(tag) = spec.strip_prefix('!') {
+                push_tag(&mut filter.none, tag);
+            } else if match_any {
+                push_tag(&mut filter.any, spec);
+            } else {
+                push_tag(&mut filter.all, spec);
+            }
+        }
+
+        filter
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.all.is_empty() && self.any.is_empty() && self.none.is_empty()
+    }
+
+    /// Whether any entry is a pattern that has to be checked against every tag's text.
+    pub fn has_globs(&self) -> bool {
+        self.all
+            .iter()
+            .chain(self.any.iter())
+            .chain(self.none.iter())
+            .any(|spec| is_pattern(spec) && !is_namespace_pattern(spec))
+    }
+
+    // Add the SQL conditions on `jots.jot_id` for this filter, binding the tag IDs as we go.
+    // Patterns are matched against the text of the `known` tags.
+    pub(crate) fn push_conditions(
+        &self,
+        conds: &mut Vec<String>,
+        args: &mut SqliteArguments,
+        known: &[Tag],
+    ) {
+        for spec in self.all.iter() {
+            conds.push(format!(
+                "jots.jot_id IN (SELECT jot_id FROM tag_map WHERE {})",
+                tag_condition(spec, known, args)
+            ));
+        }
+
+        if !self.any.is_empty() {
+            let any: Vec<String> = self
+                .any
+                .iter()
+                .map(|spec| tag_condition(spec, known, args))
+                .collect();
+            conds.push(format!(
+                "jots.jot_id IN (SELECT jot_id FROM tag_map WHERE {})",
+                any.join(" OR ")
+            ));
+        }
+
+        if !self.none.is_empty() {
+            let none: Vec<String> = self
+                .none
+                .iter()
+                .map(|spec| tag_condition(spec, known, args))
+                .collect();
+            conds.push(format!(
+                "jots.jot_id NOT IN (SELECT jot_id FROM tag_map WHERE {})",
+                none.join(" OR ")
+            ));
+        }
+    }
+}
+
+// The SQL for "tag_id is one of the tags `spec` stands for", binding whatever it needs.
+fn tag_condition(spec: &str, known: &[Tag], args: &mut SqliteArguments) -> String {
+    // a plain tag also stands for the tag it's an alias of
+    if !is_pattern(spec) {
+        args.add(spec.to_owned());
+        args.add(spec.to_owned());
+        return "tag_id IN (SELECT tag_id FROM tags WHERE tag_text = ? \
+                UNION SELECT tag_id FROM tag_aliases WHERE alias_text = ?)"
+            .to_owned();
+    }
+
+    if let (Some(namespace), "*") = split_tag(spec) {
+        args.add(namespace.to_owned());
+        return "tag_id IN (SELECT tag_id FROM tags WHERE tag_namespace = ?)".to_owned();
+    }
+
+    let glob = glob_regex(spec);
+    let ids: Vec<Uuid> = known
+        .iter()
+        .filter(|t| glob.is_match(t.text()))
+        .map(|t| t.id())
+        .collect();
+
+    if ids.is_empty() {
+        // nothing matches
+        return "0".to_owned();
+    }
+    for id in ids.iter() {
+        args.add(*id);
+    }
+    format!("tag_id IN ({})", placeholders(ids.len()))
+}
+
+fn is_pattern(spec: &str) -> bool {
+    spec.contains(&['*', '?'][..])
+}
+
+fn is_namespace_pattern(spec: &str) -> bool {
+    matches!(split_tag(spec), (Some(_), "*"))
+}
+
+fn glob_regex(glob: &str) -> Regex {
+    let mut re = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                re.push_str(".*");
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+
+    // everything but the wildcards was escaped, so this can't fail
+    Regex::new(&re).unwrap()
+}
+
+fn push_tag(tags: &mut Vec<String>, tag: &str) {
+    let tag = expand_home(tag.trim()).to_lowercase();
+    if !tag.is_empty() && !tags.contains(&tag) {
+        tags.push(tag);
+    }
+}
+
+// turn "pwd:~/src" into "pwd:/home/me/src"
+fn expand_home(tag: &str) -> String {
+    match (split_tag(tag), std::env::var("HOME")) {
+        ((Some(namespace), value), Ok(home)) if value == "~" || value.starts_with("~/") => {
+            format!("{}:{}{}", namespace, home, &value[1..])
+        }
+        _ => tag.to_owned(),
+    }
+}
+
+pub(crate) fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_star_stops_at_a_slash() {
+        let re = glob_regex("pwd:/home/*/src");
+        assert!(re.is_match("pwd:/home/me/src"));
+        assert!(re.is_match("pwd:/home//src"));
+        assert!(!re.is_match("pwd:/home/me/work/src"));
+        assert!(!re.is_match("pwd:/home/me/src/joenal"));
+    }
+
+    #[test]
+    fn double_star_crosses_slashes() {
+        let re = glob_regex("pwd:/home/me/**");
+        assert!(re.is_match("pwd:/home/me/"));
+        assert!(re.is_match("pwd:/home/me/src/joenal"));
+        assert!(!re.is_match("pwd:/home/you/src"));
+    }
+
+    #[test]
+    fn question_mark_is_one_character() {
+        let re = glob_regex("v?");
+        assert!(re.is_match("v1"));
+        assert!(!re.is_match("v"));
+        assert!(!re.is_match("v10"));
+        assert!(!re.is_match("v/"));
+    }
+
+    #[test]
+    fn everything_else_is_literal() {
+        let re = glob_regex("c++ (old).*");
+        assert!(re.is_match("c++ (old).txt"));
+        assert!(!re.is_match("cc+ (old)xtxt"));
+    }
+
+    #[test]
+    fn namespace_patterns() {
+        assert!(is_namespace_pattern("host:*"));
+        assert!(!is_namespace_pattern("host:lap*"));
+        assert!(!is_namespace_pattern("*"));
+    }
+
+    #[test]
+    fn parse_sorts_specs() {
+        let filter = TagFilter::parse(vec!["Work", " !draft", "work", "", "home"], false);
+        assert_eq!(filter.all, vec!["work", "home"]);
+        assert!(filter.any.is_empty());
+        assert_eq!(filter.none, vec!["draft"]);
+        assert!(!filter.has_globs());
+
+        let filter = TagFilter::parse(vec!["host:*", "pwd:/src/**"], true);
+        assert_eq!(filter.any, vec!["host:*", "pwd:/src/**"]);
+        assert!(filter.has_globs());
+    }
+}
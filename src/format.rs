@@ -0,0 +1,277 @@
+use std::str::FromStr;
+
+use chrono::prelude::*;
+use serde_json::json;
+
+use super::Jot;
+
+/// The ways a jot can be printed, eg, by `joenal-read --format`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JotFormat {
+    /// The `Display` layout.
+    Plain,
+    /// One JSON object per line, for `jq` and the like.
+    Json,
+    /// A heading with the date, then the content, then the tags.
+    Markdown,
+    /// Date, short ID, content and tags squeezed onto a single line.
+    Oneline,
+    /// A row of comma-separated values, after a header row of column names.
+    Csv,
+    /// A user-defined layout; see `Template`.
+    Template(Template),
+}
+
+impl FromStr for JotFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(JotFormat::Plain),
+            "json" => Ok(JotFormat::Json),
+            "markdown" | "md" => Ok(JotFormat::Markdown),
+            "oneline" => Ok(JotFormat::Oneline),
+            "csv" => Ok(JotFormat::Csv),
+            _ => Err(format!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+impl JotFormat {
+    /// What to print before the first jot, if anything.
+    pub fn header(&self) -> Option<&'static str> {
+        match self {
+            JotFormat::Csv => Some("id,date,device,tags,content_type,content"),
+            _ => None,
+        }
+    }
+
+    /// The jot, which has the given tags, laid out in this format.
+    pub fn render(&self, jot: &Jot, tags: &[String]) -> String {
+        match self {
+            JotFormat::Plain => jot.to_string(),
+            JotFormat::Json => json!({
+                "id": jot.id(),
+                "date": jot.created().map(|d| d.to_rfc3339()),
+                "device": jot.device(),
+                "tags": tags,
+                "content_type": jot.content().mime_type,
+                "content": jot.content().to_string(),
+            })
+            .to_string(),
+            JotFormat::Markdown => {
+                let mut md = format!(
+                    "## {} ({})\n\n{}\n",
+                    local_date(jot),
+                    short_id(jot),
+                    jot.content()
+                );
+                if !tags.is_empty() {
+                    md.push_str(&format!("\n*Tags: {}*\n", tags.join(", ")));
+                }
+                md
+            }
+            JotFormat::Oneline => {
+                let content = jot.content().to_string();
+                let content: Vec<&str> = content.split_whitespace().collect();
+                let tags: Vec<String> = tags.iter().map(|t| format!("#{}", t)).collect();
+                format!(
+                    "{} {} {}{}{}",
+                    local_date(jot),
+                    short_id(jot),
+                    content.join(" "),
+                    if tags.is_empty() { "" } else { " " },
+                    tags.join(" ")
+                )
+            }
+            JotFormat::Csv => [
+                jot.id().to_string(),
+                jot.created().map(|d| d.to_rfc3339()).unwrap_or_default(),
+                jot.device().to_string(),
+                tags.join(", "),
+                jot.content().mime_type.to_owned(),
+                jot.content().to_string(),
+            ]
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>()
+            .join(","),
+            JotFormat::Template(template) => template.render(jot, tags),
+        }
+    }
+}
+
+/// A layout with placeholders for parts of the jot: `{id}`, `{short_id}`, `{date}`, `{device}`,
+/// `{tags}`, `{content_type}` and `{content}`. `{{` and `}}` stand for literal braces, and `\n` and
+/// `\t` for a newline and a tab, so that templates are easy to give on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    Id,
+    ShortId,
+    Date,
+    Device,
+    Tags,
+    ContentType,
+    Content,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    text.push(c);
+                }
+                ('\\', Some('n')) => {
+                    chars.next();
+                    text.push('\n');
+                }
+                ('\\', Some('t')) => {
+                    chars.next();
+                    text.push('\t');
+                }
+                ('{', _) => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        return Err("unclosed '{' in template; use '{{' for a brace".to_owned());
+                    }
+                    let piece = match name.as_str() {
+                        "id" => Piece::Id,
+                        "short_id" => Piece::ShortId,
+                        "date" => Piece::Date,
+                        "device" => Piece::Device,
+                        "tags" => Piece::Tags,
+                        "content_type" => Piece::ContentType,
+                        "content" => Piece::Content,
+                        _ => return Err(format!("unknown placeholder '{{{}}}'", name)),
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(piece);
+                }
+                ('}', _) => {
+                    return Err("unmatched '}' in template; use '}}' for a brace".to_owned())
+                }
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+
+        Ok(Template { pieces })
+    }
+}
+
+impl Template {
+    pub fn render(&self, jot: &Jot, tags: &[String]) -> String {
+        let mut out = String::new();
+        for piece in self.pieces.iter() {
+            match piece {
+                Piece::Text(text) => out.push_str(text),
+                Piece::Id => out.push_str(&jot.id().to_string()),
+                Piece::ShortId => out.push_str(&short_id(jot)),
+                Piece::Date => {
+                    out.push_str(&jot.created().map(|d| d.to_rfc3339()).unwrap_or_default())
+                }
+                Piece::Device => out.push_str(&jot.device().to_string()),
+                Piece::Tags => out.push_str(&tags.join(", ")),
+                Piece::ContentType => out.push_str(jot.content().mime_type),
+                Piece::Content => out.push_str(&jot.content().to_string()),
+            }
+        }
+        out
+    }
+}
+
+fn short_id(jot: &Jot) -> String {
+    jot.id().to_simple().to_string()[..8].to_owned()
+}
+
+fn local_date(jot: &Jot) -> String {
+    match jot.created() {
+        Some(d) => d.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+        None => "<no date>".to_owned(),
+    }
+}
+
+// quote a field if it has anything that would confuse a CSV reader
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn jot() -> Jot {
+        Jot::new(
+            Uuid::parse_str("0a1b2c3d-4e5f-4a6b-8c7d-8e9f0a1b2c3d").unwrap(),
+            Some(Utc.timestamp_opt(1_620_142_200, 0).unwrap()),
+            b"hello {world}".to_vec(),
+            "text/plain; charset=utf-8".to_owned(),
+            Uuid::nil(),
+            None,
+            None,
+        )
+    }
+
+    fn render(template: &str) -> String {
+        let template: Template = template.parse().unwrap();
+        template.render(&jot(), &["work".to_owned(), "home".to_owned()])
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(render("{short_id} {content}"), "0a1b2c3d hello {world}");
+        assert_eq!(render("{date}"), "2021-05-04T15:30:00+00:00");
+        assert_eq!(render("[{tags}]"), "[work, home]");
+        assert_eq!(render("{content_type}"), "text/plain; charset=utf-8");
+        assert_eq!(
+            render("{id}|{device}"),
+            "0a1b2c3d-4e5f-4a6b-8c7d-8e9f0a1b2c3d|00000000-0000-0000-0000-000000000000"
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(render("{{{short_id}}}"), "{0a1b2c3d}");
+        assert_eq!(render(r"{short_id}\t{tags}\n"), "0a1b2c3d\twork, home\n");
+        assert_eq!(render(r"\x"), r"\x");
+        assert_eq!(render(""), "");
+    }
+
+    #[test]
+    fn mistakes() {
+        assert!("{nope}".parse::<Template>().is_err());
+        assert!("{content".parse::<Template>().is_err());
+        assert!("content}".parse::<Template>().is_err());
+    }
+}
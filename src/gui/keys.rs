@@ -0,0 +1,521 @@
+use druid::{
+    widget::{Controller, ListIter},
+    Data, Env, Event, EventCtx, KbKey, KeyEvent, PaintCtx, RenderContext, Selector, Widget,
+};
+
+use super::{AppState, ACTIVE_GREEN, BACK_BLUE, GLORANGE};
+use crate::{Error, Result, Shortcuts};
+
+/// Run a command, whether it came from a key, the palette or a click.
+pub const RUN_ACTION: Selector<Action> = Selector::new("joenal-gui.run-action");
+/// Give the focus to the search field.
+pub(crate) const FOCUS_SEARCH: Selector = Selector::new("joenal-gui.focus-search");
+/// Give the focus to the command palette's field.
+pub(crate) const FOCUS_PALETTE: Selector = Selector::new("joenal-gui.focus-palette");
+/// Take the focus away from any text field, so that plain keys are shortcuts again.
+pub(crate) const FOCUS_JOTS: Selector = Selector::new("joenal-gui.focus-jots");
+
+/// Everything the GUI can be told to do from the keyboard or the command palette.
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub enum Action {
+    NextJot,
+    PreviousJot,
+    Search,
+    NewJot,
+    EditJot,
+    Palette,
+    Save,
+    Cancel,
+    SortTags,
+    MatchAnyTag,
+    ShowOlder,
+}
+
+impl Action {
+    /// Every action that can be picked from the palette, in the order it lists them.
+    pub const ALL: [Action; 10] = [
+        Action::NextJot,
+        Action::PreviousJot,
+        Action::Search,
+        Action::NewJot,
+        Action::EditJot,
+        Action::Save,
+        Action::Cancel,
+        Action::SortTags,
+        Action::MatchAnyTag,
+        Action::ShowOlder,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::NextJot => "Next jot",
+            Action::PreviousJot => "Previous jot",
+            Action::Search => "Search",
+            Action::NewJot => "New jot",
+            Action::EditJot => "Edit jot",
+            Action::Palette => "Command palette",
+            Action::Save => "Save",
+            Action::Cancel => "Cancel",
+            Action::SortTags => "Sort tags by name or count",
+            Action::MatchAnyTag => "Match all or any of the tags",
+            Action::ShowOlder => "Show older jots",
+        }
+    }
+}
+
+// A key with its modifiers, parsed from something like "Ctrl+K".
+#[derive(Debug)]
+struct Shortcut {
+    key: KbKey,
+    ctrl: bool,
+    alt: bool,
+    meta: bool,
+    shift: bool,
+}
+
+impl Shortcut {
+    fn parse(spec: &str) -> Result<Self> {
+        let mut parts: Vec<&str> = spec.split('+').map(|p| p.trim()).collect();
+        // "Ctrl++" is the plus key
+        if spec.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+
+        let key = match parts.pop() {
+            Some(key) => parse_key(key).ok_or_else(|| {
+                Error::Config(format!("unknown key '{}' in shortcut '{}'", key, spec))
+            })?,
+            None => return Err(Error::Config("empty shortcut".to_owned())),
+        };
+
+        let mut shortcut = Shortcut {
+            key,
+            ctrl: false,
+            alt: false,
+            meta: false,
+            shift: false,
+        };
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => shortcut.ctrl = true,
+                "alt" | "option" => shortcut.alt = true,
+                "meta" | "cmd" | "super" => shortcut.meta = true,
+                "shift" => shortcut.shift = true,
+                _ => {
+                    return Err(Error::Config(format!(
+                        "unknown modifier '{}' in shortcut '{}'",
+                        modifier, spec
+                    )))
+                }
+            }
+        }
+
+        Ok(shortcut)
+    }
+
+    // Shift is left out for characters, since it's often needed just to type them, eg, "?".
+    fn matches(&self, event: &KeyEvent) -> bool {
+        let mods = event.mods;
+        if mods.ctrl() != self.ctrl || mods.alt() != self.alt || mods.meta() != self.meta {
+            return false;
+        }
+
+        match (&self.key, &event.key) {
+            (KbKey::Character(want), KbKey::Character(got)) => {
+                want.to_lowercase() == got.to_lowercase()
+            }
+            (want, got) => want == got && mods.shift() == self.shift,
+        }
+    }
+
+    fn has_modifier(&self) -> bool {
+        self.ctrl || self.alt || self.meta
+    }
+}
+
+fn parse_key(key: &str) -> Option<KbKey> {
+    if key.chars().count() == 1 {
+        return Some(KbKey::Character(key.to_lowercase()));
+    }
+
+    let key = match key.to_lowercase().as_str() {
+        "arrowdown" | "down" => KbKey::ArrowDown,
+        "arrowup" | "up" => KbKey::ArrowUp,
+        "arrowleft" | "left" => KbKey::ArrowLeft,
+        "arrowright" | "right" => KbKey::ArrowRight,
+        "pagedown" => KbKey::PageDown,
+        "pageup" => KbKey::PageUp,
+        "home" => KbKey::Home,
+        "end" => KbKey::End,
+        "enter" | "return" => KbKey::Enter,
+        "escape" | "esc" => KbKey::Escape,
+        "tab" => KbKey::Tab,
+        "space" => KbKey::Character(" ".to_owned()),
+        "f1" => KbKey::F1,
+        "f2" => KbKey::F2,
+        "f3" => KbKey::F3,
+        "f4" => KbKey::F4,
+        "f5" => KbKey::F5,
+        "f6" => KbKey::F6,
+        "f7" => KbKey::F7,
+        "f8" => KbKey::F8,
+        "f9" => KbKey::F9,
+        "f10" => KbKey::F10,
+        "f11" => KbKey::F11,
+        "f12" => KbKey::F12,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+/// A controller for the whole window that turns the configured keys into actions, and runs the
+/// actions the palette and buttons send as `RUN_ACTION` commands.
+pub struct KeyController {
+    shortcuts: Vec<(Shortcut, Action)>,
+}
+
+impl KeyController {
+    pub fn new(keys: &Shortcuts) -> Result<Self> {
+        let bindings = [
+            (&keys.next_jot, Action::NextJot),
+            (&keys.previous_jot, Action::PreviousJot),
+            (&keys.search, Action::Search),
+            (&keys.new_jot, Action::NewJot),
+            (&keys.edit_jot, Action::EditJot),
+            (&keys.palette, Action::Palette),
+        ];
+
+        let mut shortcuts = Vec::new();
+        for (specs, action) in bindings.iter() {
+            for spec in specs.iter() {
+                shortcuts.push((Shortcut::parse(spec)?, *action));
+            }
+        }
+
+        Ok(KeyController { shortcuts })
+    }
+
+    // the action for a key; plain keys are only shortcuts when no text field has the focus
+    fn action_for(&self, event: &KeyEvent, typing: bool) -> Option<Action> {
+        self.shortcuts
+            .iter()
+            .find(|(shortcut, _)| shortcut.matches(event) && (!typing || shortcut.has_modifier()))
+            .map(|(_, action)| *action)
+    }
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for KeyController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        match event {
+            // key events only reach widgets on the way to the focus, so the window keeps it
+            // whenever a text field doesn't
+            Event::WindowConnected | Event::MouseDown(_) => ctx.request_focus(),
+            Event::Command(cmd) if cmd.is(FOCUS_JOTS) => {
+                ctx.request_focus();
+                ctx.set_handled();
+                return;
+            }
+            Event::Command(cmd) => {
+                if let Some(action) = cmd.get(RUN_ACTION) {
+                    run_action(ctx, data, *action);
+                    ctx.set_handled();
+                    return;
+                }
+            }
+            Event::KeyDown(key) => {
+                if let Some(action) = self.action_for(key, !ctx.is_focused()) {
+                    run_action(ctx, data, action);
+                    ctx.set_handled();
+                    return;
+                }
+            }
+            _ => (),
+        }
+
+        child.event(ctx, event, data, env);
+    }
+}
+
+fn run_action(ctx: &mut EventCtx, data: &mut AppState, action: Action) {
+    if action != Action::Palette {
+        data.close_palette();
+    }
+
+    match action {
+        Action::NextJot => data.move_current(1),
+        Action::PreviousJot => data.move_current(-1),
+        Action::Search => ctx.submit_command(FOCUS_SEARCH),
+        Action::NewJot => data.start_composing(),
+        Action::EditJot => data.start_editing(),
+        Action::Palette => {
+            data.open_palette();
+            ctx.submit_command(FOCUS_PALETTE);
+        }
+        Action::Save => {
+            if data.is_composing() {
+                data.save_draft();
+            } else {
+                data.save_edit();
+            }
+        }
+        Action::Cancel => {
+            if data.is_composing() {
+                data.cancel_composing();
+            } else {
+                data.cancel_editing();
+            }
+        }
+        Action::SortTags => data.toggle_tag_order(),
+        Action::MatchAnyTag => data.toggle_match_any(),
+        Action::ShowOlder => {
+            if data.has_more() {
+                data.load_more();
+            }
+        }
+    }
+}
+
+/// A controller for the palette's field: the arrow keys pick a command, Enter runs it, and Escape
+/// closes the palette.
+pub struct PaletteController;
+
+impl<W: Widget<AppState>> Controller<AppState, W> for PaletteController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(FOCUS_PALETTE) => {
+                ctx.request_focus();
+                ctx.set_handled();
+            }
+            Event::KeyDown(key) if key.key == KbKey::ArrowDown => {
+                data.move_palette_selection(1);
+                ctx.set_handled();
+            }
+            Event::KeyDown(key) if key.key == KbKey::ArrowUp => {
+                data.move_palette_selection(-1);
+                ctx.set_handled();
+            }
+            Event::KeyDown(key) if key.key == KbKey::Enter => {
+                if let Some(action) = data.selected_action() {
+                    ctx.submit_command(RUN_ACTION.with(action));
+                }
+                ctx.submit_command(FOCUS_JOTS);
+                ctx.set_handled();
+            }
+            Event::KeyDown(key) if key.key == KbKey::Escape => {
+                data.close_palette();
+                ctx.submit_command(FOCUS_JOTS);
+                ctx.set_handled();
+            }
+            _ => {
+                let pre_data = data.palette_query.to_owned();
+                child.event(ctx, event, data, env);
+                if !data.palette_query.same(&pre_data) {
+                    data.palette_selected = 0;
+                }
+            }
+        }
+    }
+}
+
+/// One of the commands the palette lists for what's been typed in it.
+#[derive(Clone, Data, Debug)]
+pub struct PaletteEntry {
+    action: Action,
+    selected: bool,
+}
+
+impl PaletteEntry {
+    pub fn label(&self) -> &'static str {
+        self.action.name()
+    }
+
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+}
+
+impl ListIter<PaletteEntry> for AppState {
+    fn for_each(&self, mut cb: impl FnMut(&PaletteEntry, usize)) {
+        for (idx, action) in self.palette_matches().into_iter().enumerate() {
+            let entry = PaletteEntry {
+                action,
+                selected: idx == self.palette_selected,
+            };
+            cb(&entry, idx);
+        }
+    }
+
+    fn for_each_mut(&mut self, mut cb: impl FnMut(&mut PaletteEntry, usize)) {
+        for (idx, action) in self.palette_matches().into_iter().enumerate() {
+            let mut entry = PaletteEntry {
+                action,
+                selected: idx == self.palette_selected,
+            };
+            cb(&mut entry, idx);
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        self.palette_matches().len()
+    }
+}
+
+pub fn palette_entry_background(ctx: &mut PaintCtx, data: &PaletteEntry, _env: &Env) {
+    let bounds = ctx.size().to_rect();
+    if ctx.is_hot() {
+        ctx.fill(bounds, &GLORANGE);
+    } else if data.is_selected() {
+        ctx.fill(bounds, &ACTIVE_GREEN);
+    } else {
+        ctx.fill(bounds, &BACK_BLUE);
+    }
+}
+
+// The actions whose names have the letters of `query` in order, best match first: the fewer
+// letters skipped between them, the better.
+pub(crate) fn fuzzy_matches(query: &str) -> Vec<Action> {
+    let query: Vec<char> = query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    let mut found: Vec<(usize, Action)> = Action::ALL
+        .iter()
+        .filter_map(|action| fuzzy_score(&query, action.name()).map(|score| (score, *action)))
+        .collect();
+    // the sort is stable, so ties keep the palette's order
+    found.sort_by_key(|(score, _)| *score);
+
+    found.into_iter().map(|(_, action)| action).collect()
+}
+
+fn fuzzy_score(query: &[char], name: &str) -> Option<usize> {
+    let mut skipped = 0;
+    let mut wanted = query.iter().peekable();
+    let mut started = false;
+
+    for c in name.to_lowercase().chars() {
+        match wanted.peek() {
+            Some(&&w) if w == c => {
+                wanted.next();
+                started = true;
+            }
+            Some(_) if started => skipped += 1,
+            Some(_) => (),
+            None => break,
+        }
+    }
+
+    if wanted.peek().is_none() {
+        Some(skipped)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(query: &str) -> Vec<char> {
+        query.chars().collect()
+    }
+
+    #[test]
+    fn shortcut_with_modifiers() {
+        let shortcut = Shortcut::parse("Ctrl+Shift+K").unwrap();
+        assert_eq!(shortcut.key, KbKey::Character("k".to_owned()));
+        assert!(shortcut.ctrl && shortcut.shift);
+        assert!(!shortcut.alt && !shortcut.meta);
+        assert!(shortcut.has_modifier());
+    }
+
+    #[test]
+    fn shortcut_modifier_aliases_ignore_case_and_spaces() {
+        let shortcut = Shortcut::parse("control + OPTION + cmd + Enter").unwrap();
+        assert_eq!(shortcut.key, KbKey::Enter);
+        assert!(shortcut.ctrl && shortcut.alt && shortcut.meta);
+        assert!(!shortcut.shift);
+    }
+
+    #[test]
+    fn shortcut_named_and_plain_keys() {
+        assert_eq!(Shortcut::parse("down").unwrap().key, KbKey::ArrowDown);
+        assert_eq!(Shortcut::parse("Esc").unwrap().key, KbKey::Escape);
+        assert_eq!(Shortcut::parse("F12").unwrap().key, KbKey::F12);
+        assert_eq!(
+            Shortcut::parse("Space").unwrap().key,
+            KbKey::Character(" ".to_owned())
+        );
+
+        let shortcut = Shortcut::parse("?").unwrap();
+        assert_eq!(shortcut.key, KbKey::Character("?".to_owned()));
+        assert!(!shortcut.has_modifier());
+    }
+
+    #[test]
+    fn shortcut_for_the_plus_key() {
+        let shortcut = Shortcut::parse("Ctrl++").unwrap();
+        assert_eq!(shortcut.key, KbKey::Character("+".to_owned()));
+        assert!(shortcut.ctrl);
+    }
+
+    #[test]
+    fn shortcut_nonsense_is_a_config_error() {
+        assert!(matches!(
+            Shortcut::parse("Ctrl+Nope"),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(Shortcut::parse("Hyper+K"), Err(Error::Config(_))));
+        assert!(matches!(Shortcut::parse(""), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn fuzzy_score_counts_letters_skipped_after_the_first_match() {
+        assert_eq!(fuzzy_score(&chars("save"), "Save"), Some(0));
+        assert_eq!(fuzzy_score(&chars("jot"), "Next jot"), Some(0));
+        assert_eq!(fuzzy_score(&chars("nj"), "New jot"), Some(3));
+    }
+
+    #[test]
+    fn fuzzy_score_needs_every_letter_in_order() {
+        assert_eq!(fuzzy_score(&chars("tj"), "Next jot"), Some(1));
+        assert_eq!(fuzzy_score(&chars("jn"), "Next jot"), None);
+        assert_eq!(fuzzy_score(&chars("xyz"), "Next jot"), None);
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert_eq!(fuzzy_score(&[], "Search"), Some(0));
+        assert_eq!(fuzzy_matches("  "), Action::ALL.to_vec());
+    }
+
+    #[test]
+    fn fuzzy_matches_puts_the_closest_first() {
+        let found = fuzzy_matches("Ne J");
+        assert_eq!(found[0], Action::NewJot);
+        assert!(found.contains(&Action::NextJot));
+        assert!(!found.contains(&Action::Save));
+    }
+}
@@ -3,17 +3,27 @@ use druid::{
     Color, FontFamily, FontStyle, FontWeight, Selector,
 };
 use pulldown_cmark::{Event as ParseEvent, Parser, Tag};
+use regex::Regex;
 
 const BLOCKQUOTE_COLOR: Color = Color::grey8(0x88);
 const LINK_COLOR: Color = Color::rgb8(0, 0, 0xEE);
+const HIGHLIGHT_COLOR: Color = Color::rgb8(207, 91, 1);
 
 pub(crate) const OPEN_LINK: Selector<String> = Selector::new("joenal-gui.open-link");
 
 /// Parse a markdown string and generate a `RichText` object with
 /// the appropriate attributes.
 pub fn rebuild_rendered_text(text: &str) -> RichText {
+    rebuild_highlighted_text(text, "")
+}
+
+/// Like `rebuild_rendered_text`, with each word of `search` highlighted wherever it appears in the
+/// rendered text.
+pub fn rebuild_highlighted_text(text: &str, search: &str) -> RichText {
     let mut current_pos = 0;
     let mut builder = RichTextBuilder::new();
+    // the text as rendered, to find the search words in
+    let mut plain = String::new();
     let mut tag_stack = Vec::new();
 
     let parser = Parser::new(text);
@@ -24,6 +34,7 @@ pub fn rebuild_rendered_text(text: &str) -> RichText {
             }
             ParseEvent::Text(txt) => {
                 builder.push(&txt);
+                plain.push_str(&txt);
                 current_pos += txt.len();
             }
             ParseEvent::End(end_tag) => {
@@ -37,11 +48,13 @@ pub fn rebuild_rendered_text(text: &str) -> RichText {
                 );
                 if add_newline_after_tag(&tag) {
                     builder.push("\n\n");
+                    plain.push_str("\n\n");
                     current_pos += 2;
                 }
             }
             ParseEvent::Code(txt) => {
                 builder.push(&txt).font_family(FontFamily::MONOSPACE);
+                plain.push_str(&txt);
                 current_pos += txt.len();
             }
             ParseEvent::Html(txt) => {
@@ -49,18 +62,46 @@ pub fn rebuild_rendered_text(text: &str) -> RichText {
                     .push(&txt)
                     .font_family(FontFamily::MONOSPACE)
                     .text_color(BLOCKQUOTE_COLOR);
+                plain.push_str(&txt);
                 current_pos += txt.len();
             }
             ParseEvent::HardBreak => {
                 builder.push("\n\n");
+                plain.push_str("\n\n");
                 current_pos += 2;
             }
             _ => (),
         }
     }
+
+    if let Some(words) = search_regex(search) {
+        for found in words.find_iter(&plain) {
+            builder
+                .add_attributes_for_range(found.range())
+                .weight(FontWeight::BOLD)
+                .text_color(HIGHLIGHT_COLOR);
+        }
+    }
+
     builder.build()
 }
 
+// Any of the words of a search, ignoring case; a trailing `*` makes no difference, since words are
+// matched anywhere.
+fn search_regex(search: &str) -> Option<Regex> {
+    let words: Vec<String> = search
+        .split_whitespace()
+        .map(|word| word.trim_end_matches('*'))
+        .filter(|word| !word.is_empty())
+        .map(regex::escape)
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+
+    Regex::new(&format!("(?i){}", words.join("|"))).ok()
+}
+
 fn add_newline_after_tag(tag: &Tag) -> bool {
     !matches!(
         tag,
@@ -1,12 +1,15 @@
-use std::sync::Arc;
+use std::{any::Any, future::Future, sync::Arc};
 
-use crate::{Jot, Tag as JTag};
+use crate::{
+    edit_jot, get_jot, get_tags, insert_jot, list_jots, parse_tags, Identity, JotFilter,
+    JotSummary, Profile, RawJot, Tag as JTag, TagFilter, Uuid,
+};
 
 use druid::{
     text::RichText,
     widget::{Controller, ListIter},
-    AppDelegate, Color, Command, Data, DelegateCtx, Env, Event, EventCtx, Handled, Lens, PaintCtx,
-    Rect, RenderContext, Target, Widget,
+    AppDelegate, Color, Command, Data, DelegateCtx, Env, Event, EventCtx, ExtEventSink, Handled,
+    Lens, PaintCtx, Rect, RenderContext, Selector, Target, Widget,
 };
 
 use sqlx::SqlitePool;
@@ -14,6 +17,15 @@ use sqlx::SqlitePool;
 mod markdown;
 pub use markdown::*;
 
+mod keys;
+pub use keys::*;
+
+mod search;
+pub use search::*;
+
+mod sidebar;
+pub use sidebar::*;
+
 pub trait Labelable {
     fn short_label(&self, length: usize) -> String;
 }
@@ -22,18 +34,84 @@ const GLORANGE: Color = Color::rgb8(207, 91, 1);
 const BACK_BLUE: Color = Color::rgb8(5, 11, 110);
 const ACTIVE_GREEN: Color = Color::rgb8(0, 150, 5);
 
+/// How many jots to list at a time; older ones are loaded on request.
+pub const PAGE_SIZE: u32 = 200;
+
+// The content of a jot loaded in the background, to show or to edit.
+const JOT_LOADED: Selector<JotLoaded> = Selector::new("joenal-gui.jot-loaded");
+// Every tag, listed in the background.
+const TAGS_LISTED: Selector<std::result::Result<Vec<JTag>, String>> =
+    Selector::new("joenal-gui.tags-listed");
+
+struct JotLoaded {
+    id: Uuid,
+    edit: bool,
+    // the jot's content, or why it couldn't be loaded
+    content: std::result::Result<String, String>,
+}
+
 #[derive(Clone, Lens)]
 pub struct AppState {
     rendered: RichText,
     current_jot: usize,
+    // every tag, in the order the sidebar lists them
     current_tags: Arc<Vec<JTag>>,
+    tags_by_name: bool,
+    // the tags the listed jots are filtered by, and whether a jot needs all of them or any one
+    active_tags: Arc<Vec<String>>,
+    match_any: bool,
+    // what's typed in the search field, and a count of the listings and searches started, the
+    // latest of which is the only one whose jots are shown
+    search: Arc<String>,
+    listing_generation: u64,
+    // where the database work running in the background sends what it comes up with
+    sink: ExtEventSink,
     pool: sqlx::SqlitePool,
-    jots: Arc<Vec<Jot>>,
+    // only the jots listed so far, without their content
+    jots: Arc<Vec<JotSummary>>,
+    more: bool,
+    loading_more: bool,
+    // the jot being written in the compose pane, and its comma-separated tags
+    composing: bool,
+    draft: Arc<String>,
+    draft_tags: String,
+    // the journal's profile, whose tags go on every new jot, and who's writing them
+    profile: Arc<Profile>,
+    identity: Identity,
+    // the jot being edited, with its content as it is in the editor and as it was loaded
+    editing: Option<Uuid>,
+    edit_text: Arc<String>,
+    edit_original: Arc<String>,
+    // what went wrong with the last save, or why the editor can't be left yet
+    status: String,
+    // the command palette, what's typed in it, and which of the commands it lists is picked
+    palette_open: bool,
+    palette_query: Arc<String>,
+    palette_selected: usize,
 }
 
 impl Data for AppState {
     fn same(&self, other: &Self) -> bool {
-        self.current_jot == other.current_jot && self.rendered.same(&other.rendered)
+        self.current_jot == other.current_jot
+            && self.current_tags.same(&other.current_tags)
+            && self.tags_by_name == other.tags_by_name
+            && self.active_tags.same(&other.active_tags)
+            && self.match_any == other.match_any
+            && self.search.same(&other.search)
+            && self.rendered.same(&other.rendered)
+            && self.jots.same(&other.jots)
+            && self.more == other.more
+            && self.loading_more == other.loading_more
+            && self.composing == other.composing
+            && self.draft.same(&other.draft)
+            && self.draft_tags.same(&other.draft_tags)
+            && self.editing == other.editing
+            && self.edit_text.same(&other.edit_text)
+            && self.edit_original.same(&other.edit_original)
+            && self.status == other.status
+            && self.palette_open == other.palette_open
+            && self.palette_query.same(&other.palette_query)
+            && self.palette_selected == other.palette_selected
     }
 }
 
@@ -42,14 +120,512 @@ impl AppState {
         rendered: RichText,
         current_jot: usize,
         pool: SqlitePool,
-        jots: Arc<Vec<Jot>>,
+        jots: Arc<Vec<JotSummary>>,
+        profile: Profile,
+        identity: Identity,
+        tags: Vec<JTag>,
+        sink: ExtEventSink,
     ) -> Self {
+        let more = jots.len() as u32 == PAGE_SIZE;
         AppState {
             rendered,
             current_jot,
             pool,
             jots,
-            current_tags: Arc::new(vec![]),
+            more,
+            loading_more: false,
+            current_tags: Arc::new(tags),
+            tags_by_name: false,
+            active_tags: Arc::new(vec![]),
+            match_any: false,
+            search: Arc::new(String::new()),
+            listing_generation: 0,
+            sink,
+            composing: false,
+            draft: Arc::new(String::new()),
+            draft_tags: String::new(),
+            profile: Arc::new(profile),
+            identity,
+            editing: None,
+            edit_text: Arc::new(String::new()),
+            edit_original: Arc::new(String::new()),
+            status: String::new(),
+            palette_open: false,
+            palette_query: Arc::new(String::new()),
+            palette_selected: 0,
+        }
+    }
+
+    /// Show the jot `by` places further down the list, or up it if `by` is negative, listing older
+    /// jots if it's past the end.
+    pub fn move_current(&mut self, by: isize) {
+        if self.keep_editing() {
+            return;
+        }
+
+        let wanted = self.current_jot as isize + by;
+        if wanted >= self.jots.len() as isize && self.more {
+            self.load_more();
+        }
+
+        let last = self.jots.len().saturating_sub(1) as isize;
+        let idx = wanted.max(0).min(last) as usize;
+        if idx != self.current_jot {
+            self.current_jot = idx;
+            self.composing = false;
+            self.editing = None;
+            self.show_jot(idx);
+        }
+    }
+
+    /// Whether the command palette is showing.
+    pub fn is_palette_open(&self) -> bool {
+        self.palette_open
+    }
+
+    /// Show the command palette, empty.
+    pub fn open_palette(&mut self) {
+        self.palette_open = true;
+        self.palette_query = Arc::new(String::new());
+        self.palette_selected = 0;
+    }
+
+    pub fn close_palette(&mut self) {
+        self.palette_open = false;
+    }
+
+    /// The commands that match what's typed in the palette, best first.
+    pub fn palette_matches(&self) -> Vec<Action> {
+        fuzzy_matches(&self.palette_query)
+    }
+
+    /// Pick the command `by` places further down the palette's list, or up it if `by` is negative.
+    pub fn move_palette_selection(&mut self, by: isize) {
+        let last = self.palette_matches().len().saturating_sub(1) as isize;
+        self.palette_selected = (self.palette_selected as isize + by).max(0).min(last) as usize;
+    }
+
+    /// The command picked in the palette, if any match what's typed in it.
+    pub fn selected_action(&self) -> Option<Action> {
+        let matches = self.palette_matches();
+        matches
+            .get(self.palette_selected)
+            .or_else(|| matches.first())
+            .copied()
+    }
+
+    /// Whether the sidebar lists tags alphabetically rather than by how many jots have them.
+    pub fn tags_by_name(&self) -> bool {
+        self.tags_by_name
+    }
+
+    /// List the tags in the sidebar alphabetically, or most used first.
+    pub fn toggle_tag_order(&mut self) {
+        self.tags_by_name = !self.tags_by_name;
+        self.sort_tags();
+    }
+
+    /// Whether a jot needs just one of the active tags to be listed, rather than all of them.
+    pub fn is_matching_any(&self) -> bool {
+        self.match_any
+    }
+
+    /// Switch between listing jots with all of the active tags and those with any of them.
+    pub fn toggle_match_any(&mut self) {
+        if self.keep_editing() {
+            return;
+        }
+
+        self.match_any = !self.match_any;
+        if self.active_tags.len() > 1 {
+            self.reload();
+        }
+    }
+
+    /// Whether only jots with some of the tags are listed.
+    pub fn is_filtered(&self) -> bool {
+        !self.active_tags.is_empty()
+    }
+
+    /// Add the tag to the filter, or take it out if it's already there.
+    pub fn toggle_tag(&mut self, tag: &str) {
+        if self.keep_editing() {
+            return;
+        }
+
+        let mut active = (*self.active_tags).clone();
+        match active.iter().position(|t| t == tag) {
+            Some(i) => {
+                active.remove(i);
+            }
+            None => active.push(tag.to_owned()),
+        }
+        self.active_tags = Arc::new(active);
+        self.reload();
+    }
+
+    // the filter for the listed jots
+    fn filter(&self) -> JotFilter {
+        JotFilter {
+            tags: TagFilter::parse(self.active_tags.iter().map(|t| t.as_str()), self.match_any),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the list is narrowed down to the jots matching the search field.
+    pub fn is_searching(&self) -> bool {
+        !self.search.trim().is_empty()
+    }
+
+    /// Search for the text in the search field in the background, or list every jot again if it's
+    /// empty; the jots come back as a `JOTS_LISTED` command.
+    pub fn start_search(&mut self) {
+        if !self.is_searching() {
+            self.reload();
+            return;
+        }
+
+        self.listing_generation += 1;
+        let generation = self.listing_generation;
+        let pool = self.pool.clone();
+        let search = self.search.to_string();
+        let filter = self.filter();
+
+        self.spawn_load(JOTS_LISTED, async move {
+            JotsListed {
+                generation,
+                older: false,
+                jots: find_jots(&pool, &search, &filter)
+                    .await
+                    .map_err(|e| format!("Could not search the jots: {}", e)),
+                more: false,
+            }
+        });
+    }
+
+    // Run `load` in the background, and send what it comes up with back as `selector`, so that the
+    // window keeps responding however long the database takes.
+    fn spawn_load<T: Any + Send>(
+        &self,
+        selector: Selector<T>,
+        load: impl Future<Output = T> + Send + 'static,
+    ) {
+        let sink = self.sink.clone();
+        async_std::task::spawn(async move {
+            let _ = sink.submit_command(selector, load.await, Target::Auto);
+        });
+    }
+
+    // list the jots that came back from the background, unless a newer listing has started since
+    fn show_listed(&mut self, listed: &JotsListed) {
+        if listed.generation != self.listing_generation || (listed.older && !self.loading_more) {
+            return;
+        }
+
+        match &listed.jots {
+            Ok(jots) if listed.older => {
+                let mut all = (*self.jots).clone();
+                all.extend(jots.iter().cloned());
+                self.jots = Arc::new(all);
+                self.more = listed.more;
+                self.loading_more = false;
+            }
+            // a listing started before the editor was opened mustn't take the edited jot's place
+            Ok(_) if self.is_dirty() => (),
+            Ok(jots) => {
+                if !self.composing && self.editing.is_none() {
+                    self.status.clear();
+                }
+                self.show_listing(jots.clone(), listed.more);
+            }
+            Err(e) => {
+                self.status = e.clone();
+                self.loading_more = false;
+            }
+        }
+    }
+
+    // list the first page of jots that match the filter and search again in the background, and
+    // show the first when they come; never called with unsaved changes, which would be lost
+    fn reload(&mut self) {
+        self.composing = false;
+        self.editing = None;
+        if self.is_searching() {
+            self.start_search();
+            return;
+        }
+
+        self.listing_generation += 1;
+        let generation = self.listing_generation;
+        let pool = self.pool.clone();
+        let filter = self.filter();
+
+        self.spawn_load(JOTS_LISTED, async move {
+            let jots = list_jots(&pool, &filter, None, PAGE_SIZE)
+                .await
+                .map_err(|e| format!("Could not list the jots: {}", e));
+            let more = matches!(&jots, Ok(page) if page.len() as u32 == PAGE_SIZE);
+            JotsListed {
+                generation,
+                older: false,
+                jots,
+                more,
+            }
+        });
+    }
+
+    fn show_listing(&mut self, jots: Vec<JotSummary>, more: bool) {
+        self.jots = Arc::new(jots);
+        self.more = more;
+        self.loading_more = false;
+        self.current_jot = 0;
+        // an editor still open has no unsaved changes, and its jot may not be listed any more
+        self.editing = None;
+        if self.composing {
+            return;
+        }
+
+        if self.jots.is_empty() {
+            self.rendered = rebuild_rendered_text("");
+        } else {
+            self.show_jot(0);
+        }
+    }
+
+    // fetch the tags again in the background, since their counts change as jots are added
+    fn refresh_tags(&mut self) {
+        let pool = self.pool.clone();
+        self.spawn_load(TAGS_LISTED, async move {
+            get_tags(&pool)
+                .await
+                .map_err(|e| format!("Could not list the tags: {}", e))
+        });
+    }
+
+    fn show_tags(&mut self, tags: &std::result::Result<Vec<JTag>, String>) {
+        match tags {
+            Ok(tags) => {
+                self.current_tags = Arc::new(tags.clone());
+                self.sort_tags();
+            }
+            Err(e) => self.status = e.clone(),
+        }
+    }
+
+    fn sort_tags(&mut self) {
+        let mut tags = (*self.current_tags).clone();
+        if self.tags_by_name {
+            tags.sort_by(|a, b| a.text().cmp(b.text()));
+        } else {
+            tags.sort_by(|a, b| {
+                b.get_score()
+                    .cmp(&a.get_score())
+                    .then(a.text().cmp(b.text()))
+            });
+        }
+        self.current_tags = Arc::new(tags);
+    }
+
+    /// Whether the compose pane is showing instead of the current jot.
+    pub fn is_composing(&self) -> bool {
+        self.composing
+    }
+
+    /// Show the compose pane, with whatever was left in it last time.
+    pub fn start_composing(&mut self) {
+        if self.keep_editing() {
+            return;
+        }
+
+        self.composing = true;
+        self.status.clear();
+        self.editing = None;
+        self.rendered = rebuild_rendered_text(&self.draft);
+    }
+
+    /// Go back to the current jot, keeping the draft for next time.
+    pub fn cancel_composing(&mut self) {
+        self.composing = false;
+        self.status.clear();
+        self.show_jot(self.current_jot);
+    }
+
+    /// Store the draft as a new jot, and list and show it first.
+    pub fn save_draft(&mut self) {
+        if self.draft.trim().is_empty() {
+            return;
+        }
+
+        let jot = RawJot {
+            content: self.draft.to_string(),
+            creation_date: chrono::Utc::now(),
+            tags: self.profile.jot_tags(parse_tags(&self.draft_tags)),
+        };
+        let saved = async_std::task::block_on(async {
+            let id = insert_jot(&self.pool, &self.identity, &jot).await?;
+            get_jot(&self.pool, id).await
+        });
+
+        match saved {
+            Ok(jot) => {
+                let mut jots = (*self.jots).clone();
+                jots.insert(0, JotSummary::from(&jot));
+                self.jots = Arc::new(jots);
+                self.current_jot = 0;
+                self.composing = false;
+                self.draft = Arc::new(String::new());
+                self.draft_tags.clear();
+                self.status.clear();
+                self.rendered = rebuild_rendered_text(&jot.content().to_string());
+                self.refresh_tags();
+            }
+            Err(e) => self.status = format!("Could not save the jot: {}", e),
+        }
+    }
+
+    /// Whether the editor is showing for the current jot.
+    pub fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    /// Whether the jot being edited has changes that haven't been saved.
+    pub fn is_dirty(&self) -> bool {
+        self.editing.is_some() && self.edit_text != self.edit_original
+    }
+
+    /// What went wrong with the last save, or why the editor is still showing; empty if nothing.
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    // Whether there are unsaved changes that leaving the editor would lose, in which case it stays
+    // and says so.
+    pub(crate) fn keep_editing(&mut self) -> bool {
+        if self.is_dirty() {
+            self.status = "Save or cancel your changes first".to_owned();
+        }
+        self.is_dirty()
+    }
+
+    /// Whether the current jot is text, and so can be edited.
+    pub fn can_edit(&self) -> bool {
+        self.jots
+            .get(self.current_jot)
+            .map_or(false, |jot| jot.content_type().starts_with("text/"))
+    }
+
+    /// Show the editor for the current jot once its content has been loaded in full.
+    pub fn start_editing(&mut self) {
+        if !self.can_edit() || self.keep_editing() {
+            return;
+        }
+
+        self.composing = false;
+        self.load_jot(self.jots[self.current_jot].id(), true);
+    }
+
+    /// Leave the editor, throwing away any changes.
+    pub fn cancel_editing(&mut self) {
+        self.editing = None;
+        self.status.clear();
+        self.show_jot(self.current_jot);
+    }
+
+    /// Store the edited content as the jot's new content, keeping its ID, and leave the editor.
+    pub fn save_edit(&mut self) {
+        let id = match self.editing {
+            Some(id) => id,
+            None => return,
+        };
+
+        let saved = async_std::task::block_on(async {
+            edit_jot(&self.pool, id, &self.edit_text).await?;
+            get_jot(&self.pool, id).await
+        });
+
+        match saved {
+            Ok(jot) => {
+                if let Some(idx) = self.jots.iter().position(|j| j.id() == id) {
+                    let mut jots = (*self.jots).clone();
+                    jots[idx] = JotSummary::from(&jot);
+                    self.jots = Arc::new(jots);
+                }
+                self.editing = None;
+                self.status.clear();
+                self.rendered = rebuild_highlighted_text(&jot.content().to_string(), &self.search);
+            }
+            Err(e) => self.status = format!("Could not save your changes: {}", e),
+        }
+    }
+
+    /// Whether there may be older jots than the ones listed.
+    pub fn has_more(&self) -> bool {
+        self.more
+    }
+
+    /// List the next page of older jots, once they've been loaded in the background.
+    pub fn load_more(&mut self) {
+        if self.loading_more {
+            return;
+        }
+        self.loading_more = true;
+
+        let generation = self.listing_generation;
+        let pool = self.pool.clone();
+        let filter = self.filter();
+        let after = self.jots.last().map(|j| j.cursor());
+
+        self.spawn_load(JOTS_LISTED, async move {
+            let jots = list_jots(&pool, &filter, after.as_ref(), PAGE_SIZE)
+                .await
+                .map_err(|e| format!("Could not list older jots: {}", e));
+            let more = matches!(&jots, Ok(page) if page.len() as u32 == PAGE_SIZE);
+            JotsListed {
+                generation,
+                older: true,
+                jots,
+                more,
+            }
+        });
+    }
+
+    // fetch the content of the jot at `idx` in the background, and render it when it comes
+    fn show_jot(&mut self, idx: usize) {
+        if let Some(summary) = self.jots.get(idx) {
+            self.load_jot(summary.id(), false);
+        }
+    }
+
+    fn load_jot(&self, id: Uuid, edit: bool) {
+        let pool = self.pool.clone();
+        self.spawn_load(JOT_LOADED, async move {
+            let content = get_jot(&pool, id)
+                .await
+                .map(|jot| jot.content().to_string())
+                .map_err(|e| format!("Could not load the jot: {}", e));
+            JotLoaded { id, edit, content }
+        });
+    }
+
+    // show or edit a jot loaded in the background, if it's still the current one and nothing else
+    // has taken its place
+    fn show_loaded(&mut self, loaded: &JotLoaded) {
+        let current = self.jots.get(self.current_jot).map(|j| j.id());
+        if current != Some(loaded.id) || self.composing || self.editing.is_some() {
+            return;
+        }
+
+        match &loaded.content {
+            Ok(content) if loaded.edit => {
+                let content = Arc::new(content.clone());
+                self.rendered = rebuild_rendered_text(&content);
+                self.edit_text = content.clone();
+                self.edit_original = content;
+                self.editing = Some(loaded.id);
+                self.status.clear();
+            }
+            Ok(content) => self.rendered = rebuild_highlighted_text(content, &self.search),
+            Err(e) => self.status = e.clone(),
         }
     }
 }
@@ -112,10 +688,12 @@ impl ListIter<JotCard> for AppState {
             }
         }
 
-        if any_changed {
+        // picking another jot would throw away unsaved changes, so the list stays as it was
+        if any_changed && !self.keep_editing() {
             self.current_jot = new_current_jot;
-            let text = std::str::from_utf8(self.jots[new_current_jot].content().bytes).unwrap();
-            self.rendered = rebuild_rendered_text(text);
+            self.composing = false;
+            self.editing = None;
+            self.show_jot(new_current_jot);
         }
     }
 
@@ -154,30 +732,41 @@ impl<W: Widget<AppState>> Controller<AppState, W> for RichTextRebuilder {
         data: &mut AppState,
         env: &Env,
     ) {
-        let pre_data = data.current_jot;
+        let pre_draft = data.draft.to_owned();
+        let pre_edit = data.edit_text.to_owned();
         child.event(ctx, event, data, env);
-        if data.current_jot != pre_data {
-            let jot = &data.jots[data.current_jot];
-            let txt = std::str::from_utf8(jot.content().bytes).unwrap();
-            data.rendered = rebuild_rendered_text(txt);
+        if data.composing && !data.draft.same(&pre_draft) {
+            data.rendered = rebuild_rendered_text(&data.draft);
+        }
+        if data.editing.is_some() && !data.edit_text.same(&pre_edit) {
+            data.rendered = rebuild_rendered_text(&data.edit_text);
         }
     }
 }
 
 pub struct Delegate;
 
-impl<T: Data> AppDelegate<T> for Delegate {
+impl AppDelegate<AppState> for Delegate {
     fn command(
         &mut self,
         _ctx: &mut DelegateCtx,
         _target: Target,
         cmd: &Command,
-        _data: &mut T,
+        data: &mut AppState,
         _env: &Env,
     ) -> Handled {
         if let Some(url) = cmd.get(OPEN_LINK) {
             open::that_in_background(url);
             Handled::Yes
+        } else if let Some(listed) = cmd.get(JOTS_LISTED) {
+            data.show_listed(listed);
+            Handled::Yes
+        } else if let Some(loaded) = cmd.get(JOT_LOADED) {
+            data.show_loaded(loaded);
+            Handled::Yes
+        } else if let Some(tags) = cmd.get(TAGS_LISTED) {
+            data.show_tags(tags);
+            Handled::Yes
         } else {
             Handled::No
         }
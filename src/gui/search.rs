@@ -0,0 +1,71 @@
+use druid::{widget::Controller, Data, Env, Event, EventCtx, KbKey, Selector, Widget};
+use sqlx::SqlitePool;
+
+use super::{AppState, FOCUS_JOTS, FOCUS_SEARCH, PAGE_SIZE};
+use crate::{search_jots_filtered, JotFilter, JotSummary};
+
+/// Jots listed or found by a search that ran in the background.
+pub(crate) const JOTS_LISTED: Selector<JotsListed> = Selector::new("joenal-gui.jots-listed");
+
+pub(crate) struct JotsListed {
+    // which listing these belong to, so that those of an outdated one can be dropped
+    pub(crate) generation: u64,
+    // whether they're older jots to go after the ones already listed, rather than a new listing
+    pub(crate) older: bool,
+    // the jots, or why they couldn't be listed
+    pub(crate) jots: std::result::Result<Vec<JotSummary>, String>,
+    // whether there may be older jots still
+    pub(crate) more: bool,
+}
+
+/// A controller for the search field that starts a new search whenever its text changes, unless
+/// there are unsaved edits, takes the focus when asked to with `FOCUS_SEARCH`, and gives it back to
+/// the jots on Escape.
+pub struct SearchController;
+
+impl<W: Widget<AppState>> Controller<AppState, W> for SearchController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(FOCUS_SEARCH) => {
+                ctx.request_focus();
+                ctx.set_handled();
+                return;
+            }
+            Event::KeyDown(key) if key.key == KbKey::Escape && ctx.is_focused() => {
+                ctx.submit_command(FOCUS_JOTS);
+                ctx.set_handled();
+                return;
+            }
+            _ => (),
+        }
+
+        let pre_data = data.search.to_owned();
+        child.event(ctx, event, data, env);
+        if !data.search.same(&pre_data) {
+            // a new listing would take the place of the jot being edited
+            if data.keep_editing() {
+                data.search = pre_data;
+            } else {
+                data.start_search();
+            }
+        }
+    }
+}
+
+// The best page of the jots matching `search` among those selected by `filter`, best match first.
+pub(crate) async fn find_jots(
+    pool: &SqlitePool,
+    search: &str,
+    filter: &JotFilter,
+) -> crate::Result<Vec<JotSummary>> {
+    let hits = search_jots_filtered(pool, search, filter, Some(PAGE_SIZE)).await?;
+
+    Ok(hits.iter().map(|hit| JotSummary::from(&hit.jot)).collect())
+}
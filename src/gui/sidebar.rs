@@ -0,0 +1,139 @@
+use druid::{widget::ListIter, Data, Env, PaintCtx, RenderContext};
+
+use super::{AppState, ACTIVE_GREEN, BACK_BLUE, GLORANGE};
+
+/// A tag in the sidebar, with how many jots have it; clicking it adds it to or takes it out of
+/// the filter.
+#[derive(Clone, Data, Debug)]
+pub struct TagRow {
+    text: String,
+    score: i32,
+    active: bool,
+}
+
+impl TagRow {
+    pub fn label(&self) -> String {
+        format!("{} ({})", self.text, self.score)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+    }
+}
+
+/// A tag the jots are being filtered by, shown above the list; clicking it removes it from the
+/// filter.
+#[derive(Clone, Data, Debug)]
+pub struct TagChip {
+    text: String,
+    removed: bool,
+}
+
+impl TagChip {
+    pub fn label(&self) -> String {
+        format!("{} \u{d7}", self.text)
+    }
+
+    pub fn remove(&mut self) {
+        self.removed = true;
+    }
+}
+
+impl ListIter<TagRow> for AppState {
+    fn for_each(&self, mut cb: impl FnMut(&TagRow, usize)) {
+        for (idx, tag) in self.current_tags.iter().enumerate() {
+            let row = TagRow {
+                text: tag.text().to_owned(),
+                score: tag.get_score(),
+                active: self.active_tags.iter().any(|t| t == tag.text()),
+            };
+            cb(&row, idx);
+        }
+    }
+
+    fn for_each_mut(&mut self, mut cb: impl FnMut(&mut TagRow, usize)) {
+        let mut toggled = None;
+
+        for (idx, tag) in self.current_tags.iter().enumerate() {
+            let active = self.active_tags.iter().any(|t| t == tag.text());
+            let mut row = TagRow {
+                text: tag.text().to_owned(),
+                score: tag.get_score(),
+                active,
+            };
+            cb(&mut row, idx);
+
+            if row.active != active {
+                toggled = Some(row.text);
+            }
+        }
+
+        if let Some(tag) = toggled {
+            self.toggle_tag(&tag);
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        self.current_tags.len()
+    }
+}
+
+impl ListIter<TagChip> for AppState {
+    fn for_each(&self, mut cb: impl FnMut(&TagChip, usize)) {
+        for (idx, tag) in self.active_tags.iter().enumerate() {
+            let chip = TagChip {
+                text: tag.clone(),
+                removed: false,
+            };
+            cb(&chip, idx);
+        }
+    }
+
+    fn for_each_mut(&mut self, mut cb: impl FnMut(&mut TagChip, usize)) {
+        let mut removed = None;
+
+        for (idx, tag) in self.active_tags.iter().enumerate() {
+            let mut chip = TagChip {
+                text: tag.clone(),
+                removed: false,
+            };
+            cb(&mut chip, idx);
+
+            if chip.removed {
+                removed = Some(chip.text);
+            }
+        }
+
+        if let Some(tag) = removed {
+            self.toggle_tag(&tag);
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        self.active_tags.len()
+    }
+}
+
+pub fn tag_row_background(ctx: &mut PaintCtx, data: &TagRow, _env: &Env) {
+    let bounds = ctx.size().to_rect();
+    if ctx.is_hot() {
+        ctx.fill(bounds, &GLORANGE);
+    } else if data.is_active() {
+        ctx.fill(bounds, &ACTIVE_GREEN);
+    } else {
+        ctx.fill(bounds, &BACK_BLUE);
+    }
+}
+
+pub fn tag_chip_background(ctx: &mut PaintCtx, _data: &TagChip, _env: &Env) {
+    let bounds = ctx.size().to_rect().to_rounded_rect(8.0);
+    if ctx.is_hot() {
+        ctx.fill(bounds, &GLORANGE);
+    } else {
+        ctx.fill(bounds, &ACTIVE_GREEN);
+    }
+}
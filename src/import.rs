@@ -0,0 +1,721 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::prelude::*;
+use lazy_static::lazy_static;
+use mime::TEXT_PLAIN_UTF_8;
+use regex::Regex;
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use super::{
+    create_temp_file, get_tombstones, insert_jot_content, insert_link, jot_exists, make_pool,
+    merge_database, mk_jot_id, parse_date_span, remove_db_file, Error, Identity, JotOrigin,
+    LinkType, MergeReport, RawJot, Record, Result, StarDate, Uuid,
+};
+
+/// The kinds of journal `import_jots` understands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// A directory of Markdown files, optionally with YAML front matter giving each one's date and
+    /// tags, such as one written by `export_markdown`.
+    Markdown,
+    /// A plain-text journal from jrnl, with `@tags` in the text.
+    Jrnl,
+    /// The JSON file from a Day One export.
+    DayOne,
+}
+
+impl ImportFormat {
+    /// Guess the format from the path: a directory is Markdown, a `.json` file is Day One, and
+    /// anything else is jrnl.
+    pub fn guess(path: &Path) -> Self {
+        if path.is_dir() {
+            ImportFormat::Markdown
+        } else if path.extension().and_then(|e| e.to_str()) == Some("json") {
+            ImportFormat::DayOne
+        } else {
+            ImportFormat::Jrnl
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(ImportFormat::Markdown),
+            "jrnl" => Ok(ImportFormat::Jrnl),
+            "dayone" | "day-one" => Ok(ImportFormat::DayOne),
+            _ => Err(format!("unknown import format '{}'", s)),
+        }
+    }
+}
+
+/// What `import_jots` did with the entries it found.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub jots_added: u64,
+    /// Entries already in the database, eg, from an earlier run of the same import.
+    pub jots_existing: u64,
+    /// Entries for jots that were deleted here, which stay deleted.
+    pub jots_deleted: u64,
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "jots added:    {}", self.jots_added)?;
+        writeln!(f, "already here:  {}", self.jots_existing)?;
+        writeln!(f, "left deleted:  {}", self.jots_deleted)
+    }
+}
+
+/// Bring in the entries of another journal at `path`. Each entry becomes a jot with the entry's
+/// date and tags, under the same deterministic ID that `insert_jot` would give it for `identity`,
+/// or under the `jot_id` in its front matter if it has one; entries already present or deleted are
+/// skipped, so running the same import again changes nothing.
+pub async fn import_jots(
+    pool: &SqlitePool,
+    identity: &Identity,
+    path: &Path,
+    format: ImportFormat,
+) -> Result<ImportReport> {
+    let entries = match format {
+        ImportFormat::Markdown => read_markdown_dir(path)?,
+        ImportFormat::Jrnl => read_jrnl(&fs::read_to_string(path)?),
+        ImportFormat::DayOne => read_day_one(&fs::read_to_string(path)?)?,
+    };
+
+    let deleted: HashSet<Uuid> = get_tombstones(pool).await?.iter().map(|t| t.id()).collect();
+
+    let mut report = ImportReport::default();
+    for entry in entries.iter() {
+        let id = match entry.id {
+            Some(id) => id,
+            None => mk_jot_id(identity, &entry.jot),
+        };
+        if deleted.contains(&id) {
+            report.jots_deleted += 1;
+            continue;
+        }
+        if jot_exists(pool, id).await? {
+            report.jots_existing += 1;
+            continue;
+        }
+
+        let (content, mime_type) = match &entry.attachment {
+            Some((bytes, mime_type)) => (bytes.as_slice(), mime_type.as_str()),
+            None => (entry.jot.content.as_bytes(), entry.mime_type.as_str()),
+        };
+        let origin = match &entry.origin {
+            Some(origin) => origin.clone(),
+            None => JotOrigin::local(identity, entry.jot.creation_date),
+        };
+        let _ = insert_jot_content(
+            pool,
+            identity,
+            id,
+            content,
+            mime_type,
+            &origin,
+            &entry.jot.tags,
+        )
+        .await?;
+        report.jots_added += 1;
+    }
+
+    // links can point either way between entries, so they wait until every jot is in
+    for entry in entries.iter().filter(|e| e.id.is_some()) {
+        let from = entry.id.unwrap();
+        for (link_type, to) in entry.links.iter() {
+            if jot_exists(pool, from).await? && jot_exists(pool, *to).await? {
+                insert_link(pool, from, *to, *link_type).await?;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Bring in an NDJSON export, as written by `export_ndjson`. The records are loaded into a scratch
+/// database which is then merged into this one, so every row keeps its ID, device and content
+/// exactly, and the usual merge rules apply to jots that were edited or deleted on either side.
+pub async fn import_ndjson(pool: &SqlitePool, input: impl BufRead) -> Result<MergeReport> {
+    let mut records = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = serde_json::from_str(&line)
+            .map_err(|e| Error::Encoding(format!("bad record on line {}: {}", i + 1, e)))?;
+        records.push(record);
+    }
+    // rows that refer to tags and jots have to come after them
+    records.sort_by_key(record_rank);
+
+    let (scratch, file) = create_temp_file(".db")?;
+    drop(file);
+
+    let report = merge_records(pool, &scratch, &records).await;
+
+    remove_db_file(&scratch);
+
+    report
+}
+
+async fn merge_records(
+    pool: &SqlitePool,
+    scratch: &Path,
+    records: &[Record],
+) -> Result<MergeReport> {
+    let other = make_pool(&scratch.to_string_lossy()).await?;
+
+    let mut tx = other.begin().await?;
+    for record in records.iter() {
+        let insert = match record {
+            Record::Tag(tag) => tag.as_insert(),
+            Record::Jot(jot) => jot.as_insert(),
+            Record::Mapping(mapping) => mapping.as_insert(),
+            Record::TagAlias(alias) => alias.as_insert(),
+            Record::Link(link) => link.as_insert(),
+            Record::Revision(revision) => revision.as_insert(),
+            Record::Tombstone(tombstone) => tombstone.as_insert(),
+            Record::JournalKey(key) => key.as_insert(),
+        };
+        let _ = insert.execute(&mut tx).await?;
+    }
+    tx.commit().await?;
+    other.close().await;
+
+    merge_database(pool, scratch).await
+}
+
+fn record_rank(record: &Record) -> u8 {
+    match record {
+        Record::Tag(_) | Record::Jot(_) | Record::JournalKey(_) => 0,
+        _ => 1,
+    }
+}
+
+// One jot's worth of an imported journal.
+struct Entry {
+    jot: RawJot,
+    id: Option<Uuid>,
+    mime_type: String,
+    // the bytes and MIME type of a jot that isn't text
+    attachment: Option<(Vec<u8>, String)>,
+    links: Vec<(LinkType, Uuid)>,
+    // for a jot exported from joenal, the date, device, duplicate and author it was stored with
+    origin: Option<JotOrigin>,
+}
+
+impl Entry {
+    fn new(content: String, creation_date: StarDate, tags: Vec<String>) -> Self {
+        Entry {
+            jot: RawJot {
+                content,
+                creation_date,
+                tags,
+            },
+            id: None,
+            mime_type: TEXT_PLAIN_UTF_8.to_string(),
+            attachment: None,
+            links: Vec::new(),
+            origin: None,
+        }
+    }
+}
+
+fn read_markdown_dir(dir: &Path) -> Result<Vec<Entry>> {
+    if !dir.is_dir() {
+        return Err(Error::NotFound(format!(
+            "{} is not a directory",
+            dir.display()
+        )));
+    }
+
+    let mut paths = Vec::new();
+    find_markdown(dir, &mut paths)?;
+    paths.sort();
+
+    let mut entries = Vec::with_capacity(paths.len());
+    for path in paths.iter() {
+        let entry = read_markdown(path).map_err(|e| {
+            let msg = format!("could not import {}: {}", path.display(), e);
+            match e {
+                Error::Io(e) => Error::Io(io::Error::new(e.kind(), msg)),
+                _ => Error::Encoding(msg),
+            }
+        })?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+fn find_markdown(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_markdown(&path, paths)?;
+        } else if path.extension().and_then(|e| e.to_str()) == Some("md") {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// A Markdown file, whose front matter may give its `jot_id`, date and tags; without a date, the
+// file's modification time is used. A file exported from joenal, with a `device_id` as well as a
+// `jot_id`, brings back the jot's row as it was: its date, which may be null, device, duplicate and
+// author.
+fn read_markdown(path: &Path) -> Result<Entry> {
+    let text = fs::read_to_string(path)?;
+
+    let (front, body) = match text.strip_prefix("---\n") {
+        Some(rest) => match rest.find("\n---\n") {
+            Some(end) => (&rest[..=end], &rest[end + 5..]),
+            None if rest.ends_with("\n---") => (&rest[..rest.len() - 3], ""),
+            None => ("", text.as_str()),
+        },
+        None => ("", text.as_str()),
+    };
+    let front = FrontMatter::parse(front);
+
+    let id = front.uuid("jot_id")?;
+    let device_id = front.uuid("device_id")?;
+    let exported = id.is_some() && device_id.is_some();
+    if exported && !front.has("creation_date") {
+        return Err(Error::Encoding(
+            "a jot exported from joenal needs its creation_date, even if it's null".to_owned(),
+        ));
+    }
+
+    let date = match ["creation_date", "date", "created"]
+        .iter()
+        .find_map(|key| front.get(key))
+    {
+        Some(date) => match parse_date_span(&date) {
+            Some(span) => Some(span.start),
+            None => {
+                return Err(Error::Encoding(format!(
+                    "can't understand the date '{}'",
+                    date
+                )))
+            }
+        },
+        None => None,
+    };
+    // an exported jot without a date is stored without one, so this is only for making an ID
+    let creation_date = match date {
+        Some(date) => date,
+        None => fs::metadata(path)?.modified()?.into(),
+    };
+    let mut tags: Vec<String> = front
+        .list("tags")
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if tags.is_empty() && id.is_none() {
+        tags.push("untagged".to_owned());
+    }
+
+    // a jot that came from joenal keeps its text exactly as it was
+    let content = if id.is_some() { body } else { body.trim() };
+    let mut entry = Entry::new(content.to_owned(), creation_date, tags);
+    entry.id = id;
+    if let (Some(_), Some(device_id)) = (id, device_id) {
+        entry.origin = Some(JotOrigin {
+            creation_date: date,
+            device_id,
+            dup_id: front.uuid("dup_id")?,
+            author_id: front.uuid("author_id")?,
+        });
+    }
+
+    if let Some(mime_type) = front.get("content_type") {
+        entry.mime_type = mime_type.clone();
+        if let Some(file) = front.get("attachment") {
+            let file = path.with_file_name(file);
+            entry.attachment = Some((fs::read(&file)?, mime_type));
+        }
+    }
+
+    for link in front.list("links") {
+        let mut words = link.split_whitespace();
+        if let (Some(link_type), Some(to)) = (words.next(), words.next()) {
+            let link_type = link_type.parse().map_err(Error::Encoding)?;
+            entry.links.push((link_type, Uuid::parse_str(to)?));
+        }
+    }
+
+    Ok(entry)
+}
+
+// Just enough YAML to read front matter: `key: value` pairs whose values are scalars, flow lists
+// like `[a, b]`, or block lists of `- item` lines.
+struct FrontMatter {
+    // each key's values, and whether they were written as one unquoted scalar
+    pairs: Vec<(String, Vec<String>, bool)>,
+}
+
+impl FrontMatter {
+    fn parse(text: &str) -> Self {
+        let mut pairs: Vec<(String, Vec<String>, bool)> = Vec::new();
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if let Some(item) = trimmed.strip_prefix("- ") {
+                if let Some((_, values, _)) = pairs.last_mut() {
+                    values.push(yaml_scalar(item));
+                }
+            } else if let Some(colon) = trimmed.find(':') {
+                let key = trimmed[..colon].trim().to_owned();
+                let value = trimmed[colon + 1..].trim();
+                let plain = !value.starts_with(&['"', '\'', '['][..]);
+                let values = if value.is_empty() || value == "null" || value == "~" {
+                    Vec::new()
+                } else if value.starts_with('[') && value.ends_with(']') {
+                    value[1..value.len() - 1]
+                        .split(',')
+                        .map(yaml_scalar)
+                        .filter(|v| !v.is_empty())
+                        .collect()
+                } else {
+                    vec![yaml_scalar(value)]
+                };
+                pairs.push((key, values, plain));
+            }
+        }
+
+        FrontMatter { pairs }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.pairs
+            .iter()
+            .find(|(k, _, _)| k == key)
+            .and_then(|(_, values, _)| values.first().cloned())
+    }
+
+    // whether the key is there at all, even if it's null
+    fn has(&self, key: &str) -> bool {
+        self.pairs.iter().any(|(k, _, _)| k == key)
+    }
+
+    fn uuid(&self, key: &str) -> Result<Option<Uuid>> {
+        match self.get(key) {
+            Some(id) => Ok(Some(Uuid::parse_str(&id)?)),
+            None => Ok(None),
+        }
+    }
+
+    // a list, or a single comma-separated value like "tags: work, home"
+    fn list(&self, key: &str) -> Vec<String> {
+        match self.pairs.iter().find(|(k, _, _)| k == key) {
+            Some((_, values, true)) if values.len() == 1 => {
+                values[0].split(',').map(|v| v.trim().to_owned()).collect()
+            }
+            Some((_, values, _)) => values.clone(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn yaml_scalar(text: &str) -> String {
+    let text = text.trim();
+
+    if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        return text[1..text.len() - 1].replace("''", "'");
+    }
+    if !(text.len() >= 2 && text.starts_with('"') && text.ends_with('"')) {
+        return text.to_owned();
+    }
+
+    let mut value = String::with_capacity(text.len());
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('0') => value.push('\0'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    value.push(c);
+                }
+            }
+            Some(c) => value.push(c),
+            None => {}
+        }
+    }
+
+    value
+}
+
+// A jrnl journal: each entry starts with a line like "2021-05-04 15:30 Title" or, in jrnl's text
+// export, "[2021-05-04 15:30] Title", in local time, and runs until the next one.
+fn read_jrnl(text: &str) -> Vec<Entry> {
+    lazy_static! {
+        static ref HEADER: Regex =
+            Regex::new(r"^\[?(\d{4}-\d{2}-\d{2} \d{2}:\d{2}(?::\d{2})?)\]?(?: \*)? ?(.*)$")
+                .unwrap();
+        static ref TAG: Regex = Regex::new(r"(?:^|\s)@([\w/:.-]*\w)").unwrap();
+    }
+
+    let mut entries = Vec::new();
+    let mut current: Option<(StarDate, String)> = None;
+
+    let mut finish = |current: Option<(StarDate, String)>| {
+        if let Some((date, content)) = current {
+            let content = content.trim().to_owned();
+            let mut tags: Vec<String> = Vec::new();
+            for cap in TAG.captures_iter(&content) {
+                let tag = cap[1].to_lowercase();
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            if tags.is_empty() {
+                tags.push("untagged".to_owned());
+            }
+            entries.push(Entry::new(content, date, tags));
+        }
+    };
+
+    for line in text.lines() {
+        let header = HEADER
+            .captures(line)
+            .and_then(|cap| Some((parse_date_span(&cap[1])?.start, cap[2].to_owned())));
+        match header {
+            Some((date, title)) => {
+                finish(current.take());
+                current = Some((date, title));
+            }
+            None => {
+                if let Some((_, content)) = current.as_mut() {
+                    content.push('\n');
+                    content.push_str(line);
+                }
+            }
+        }
+    }
+    finish(current);
+
+    entries
+}
+
+#[derive(Deserialize)]
+struct DayOneExport {
+    entries: Vec<DayOneEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DayOneEntry {
+    creation_date: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+// The Journal.json from a Day One export, whose dates are UTC.
+fn read_day_one(text: &str) -> Result<Vec<Entry>> {
+    let export: DayOneExport = serde_json::from_str(text)?;
+
+    let mut entries = Vec::with_capacity(export.entries.len());
+    for e in export.entries {
+        let creation_date = DateTime::parse_from_rfc3339(&e.creation_date)?.with_timezone(&Utc);
+        let mut tags: Vec<String> = e
+            .tags
+            .iter()
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty())
+            .collect();
+        if tags.is_empty() {
+            tags.push("untagged".to_owned());
+        }
+        entries.push(Entry::new(e.text.trim().to_owned(), creation_date, tags));
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    use super::*;
+    use crate::{create_temp_file, export_markdown, get_jot, Jot};
+
+    #[test]
+    fn scalars() {
+        assert_eq!(yaml_scalar("  plain words "), "plain words");
+        assert_eq!(yaml_scalar("'it''s'"), "it's");
+        assert_eq!(
+            yaml_scalar(r#""say \"hi\"\n\tC:\\temp\u0007""#),
+            "say \"hi\"\n\tC:\\temp\u{7}"
+        );
+        assert_eq!(yaml_scalar(r#""naïve ☕""#), "naïve ☕");
+        // a lone quote isn't a quoted string
+        assert_eq!(yaml_scalar("\""), "\"");
+    }
+
+    #[test]
+    fn front_matter_values() {
+        let front = FrontMatter::parse(
+            "# exported\njot_id: 0a1b2c3d\ndate: \"2021-05-04 15:30\"\ncontent_type: null\n",
+        );
+        assert_eq!(front.get("jot_id").as_deref(), Some("0a1b2c3d"));
+        assert_eq!(front.get("date").as_deref(), Some("2021-05-04 15:30"));
+        assert_eq!(front.get("content_type"), None);
+        assert!(front.has("content_type"));
+        assert_eq!(front.get("missing"), None);
+        assert!(!front.has("missing"));
+    }
+
+    #[test]
+    fn front_matter_lists() {
+        let front = FrontMatter::parse(
+            r#"
+tags:
+  - "work"
+  - 'home'
+links: []
+flow: [a, "b", ]
+plain: work, home
+quoted: "work, home"
+"#,
+        );
+        assert_eq!(front.list("tags"), vec!["work", "home"]);
+        assert!(front.list("links").is_empty());
+        assert_eq!(front.list("flow"), vec!["a", "b"]);
+        assert_eq!(front.list("plain"), vec!["work", "home"]);
+        assert_eq!(front.list("quoted"), vec!["work, home"]);
+        assert!(front.list("missing").is_empty());
+    }
+
+    // everything in a jot's row, since jots are equal when their IDs are
+    type Row = (
+        Uuid,
+        Option<StarDate>,
+        Vec<u8>,
+        String,
+        Uuid,
+        Option<Uuid>,
+        Option<Uuid>,
+    );
+
+    fn row(jot: &Jot) -> Row {
+        let content = jot.content();
+        (
+            jot.id(),
+            jot.created(),
+            content.bytes.to_vec(),
+            content.mime_type.to_owned(),
+            jot.device(),
+            jot.dup(),
+            jot.author(),
+        )
+    }
+
+    #[async_std::test]
+    async fn markdown_round_trip_keeps_the_whole_row() {
+        let date = Utc.from_utc_datetime(
+            &NaiveDate::from_ymd_opt(2021, 5, 4)
+                .unwrap()
+                .and_hms_opt(15, 30, 0)
+                .unwrap(),
+        );
+        let text = "text/plain; charset=utf-8".to_owned();
+        let jots = [
+            Jot::new(
+                Uuid::new_v4(),
+                Some(date),
+                b"dear diary\n\n".to_vec(),
+                text.clone(),
+                Uuid::new_v4(),
+                Some(Uuid::new_v4()),
+                Some(Uuid::new_v4()),
+            ),
+            Jot::new(
+                Uuid::new_v4(),
+                None,
+                b"no date, device or author of ours".to_vec(),
+                text,
+                Uuid::new_v4(),
+                None,
+                None,
+            ),
+        ];
+
+        let (from_path, file) = create_temp_file(".db").unwrap();
+        drop(file);
+        let (to_path, file) = create_temp_file(".db").unwrap();
+        drop(file);
+        let dir = std::env::temp_dir().join(format!("joenal-{}", Uuid::new_v4().to_simple()));
+
+        let from = make_pool(&from_path.to_string_lossy()).await.unwrap();
+        for jot in jots.iter() {
+            let _ = jot.as_insert().execute(&from).await.unwrap();
+        }
+        assert_eq!(export_markdown(&from, &dir).await.unwrap(), 2);
+
+        let to = make_pool(&to_path.to_string_lossy()).await.unwrap();
+        let identity = Identity {
+            device_id: Uuid::new_v4(),
+            author_id: None,
+        };
+        let report = import_jots(&to, &identity, &dir, ImportFormat::Markdown)
+            .await
+            .unwrap();
+        assert_eq!(report.jots_added, 2);
+        for jot in jots.iter() {
+            assert_eq!(row(&get_jot(&to, jot.id()).await.unwrap()), row(jot));
+        }
+
+        from.close().await;
+        to.close().await;
+        remove_db_file(&from_path);
+        remove_db_file(&to_path);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn exported_jot_needs_its_date() {
+        let (path, file) = create_temp_file(".md").unwrap();
+        drop(file);
+        fs::write(
+            &path,
+            format!(
+                "---\njot_id: {}\ndevice_id: {}\n---\nundated\n",
+                Uuid::new_v4(),
+                Uuid::new_v4()
+            ),
+        )
+        .unwrap();
+
+        assert!(matches!(read_markdown(&path), Err(Error::Encoding(_))));
+        let _ = fs::remove_file(&path);
+    }
+}
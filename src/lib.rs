@@ -1,19 +1,42 @@
 use std::collections::BTreeSet;
 
-use anyhow::Result as AResult;
 use lazy_static::lazy_static;
 use mime::TEXT_PLAIN_UTF_8;
-use sqlx::{query, query_as, query_scalar, sqlite::SqlitePool};
+use sqlx::{
+    query, query_as, query_as_with, query_scalar,
+    sqlite::{SqliteArguments, SqlitePool},
+    Arguments, Sqlite, Transaction,
+};
 use uuid::Uuid;
 
+mod crypt;
+mod dates;
 mod db;
+mod diff;
+mod error;
+mod export;
+mod filter;
+mod format;
 pub mod gui;
+mod import;
+mod merge;
 mod models;
+mod tags;
 mod util;
 
+pub use crypt::*;
+pub use dates::*;
 pub use db::*;
+pub use diff::*;
+pub use error::*;
+pub use export::*;
+pub use filter::*;
+pub use format::*;
 pub use gui::*;
+pub use import::*;
+pub use merge::*;
 pub use models::*;
+pub use tags::*;
 pub use util::*;
 
 pub type StarDate = chrono::DateTime<chrono::Utc>;
@@ -25,101 +48,745 @@ pub struct RawJot {
     pub tags: Vec<String>,
 }
 
-pub async fn insert_jot(pool: &SqlitePool, jot: &RawJot) -> AResult<()> {
+/// Insert a new jot and its tags as written by `identity`, returning the ID it was stored under.
+pub async fn insert_jot(pool: &SqlitePool, identity: &Identity, jot: &RawJot) -> Result<Uuid> {
     lazy_static! {
         static ref UTF_8_MIME: String = TEXT_PLAIN_UTF_8.to_string();
     }
 
-    let mut jot_id = mk_jot_id(jot);
-    let dev_id = get_device_id();
+    insert_jot_content(
+        pool,
+        identity,
+        mk_jot_id(identity, jot),
+        jot.content.as_bytes(),
+        &UTF_8_MIME,
+        &JotOrigin::local(identity, jot.creation_date),
+        &jot.tags,
+    )
+    .await
+}
 
-    let mut dup_id = None;
+// When a jot was written, on which device and by whom, and what it's a duplicate of, as stored in
+// its row. A jot written here is this device's and its author's; one brought back from an export
+// keeps what it was exported with.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct JotOrigin {
+    pub(crate) creation_date: Option<StarDate>,
+    pub(crate) device_id: Uuid,
+    pub(crate) dup_id: Option<Uuid>,
+    pub(crate) author_id: Option<Uuid>,
+}
+
+impl JotOrigin {
+    pub(crate) fn local(identity: &Identity, creation_date: StarDate) -> Self {
+        JotOrigin {
+            creation_date: Some(creation_date),
+            device_id: identity.device_id,
+            dup_id: None,
+            author_id: identity.author_id,
+        }
+    }
+}
 
+// Insert a jot with the given ID, content, origin and tags, as `insert_jot` does for text, making
+// the IDs of new tags as `identity`. Importing uses this directly to bring jots back under the IDs
+// and origins they were exported with.
+pub(crate) async fn insert_jot_content(
+    pool: &SqlitePool,
+    identity: &Identity,
+    jot_id: Uuid,
+    content: &[u8],
+    mime_type: &str,
+    origin: &JotOrigin,
+    tags: &[String],
+) -> Result<Uuid> {
     // do everything in a single transaction
     let mut tx = pool.begin().await?;
 
-    let jot_count: u32 = query_scalar(
-        r#"
-SELECT COUNT(*) FROM jots WHERE jot_id = ?1
-"#,
+    let jot_id = insert_jot_row(
+        &mut tx,
+        jot_id,
+        content.to_vec(),
+        mime_type.to_owned(),
+        origin.clone(),
     )
-    .bind(&jot_id)
-    .fetch_one(&mut tx)
     .await?;
 
-    if jot_count > 0 {
-        dup_id = Some(jot_id);
-        jot_id = Uuid::new_v4();
-    };
-
-    let new_jot = models::Jot::new(
-        jot_id,
-        Some(jot.creation_date),
-        jot.content.as_bytes().to_vec(),
-        UTF_8_MIME.clone(),
-        dev_id,
-        dup_id,
-    );
-
-    let _ = new_jot.as_insert().execute(&mut tx).await?;
+    // aliases are stored as the tag they stand for, which may make some tags the same
+    let mut resolved: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags.iter() {
+        let tag = resolve_alias(&mut tx, identity, tag).await?;
+        if !resolved.contains(&tag) {
+            resolved.push(tag);
+        }
+    }
 
-    for tag in jot.tags.iter() {
-        let id = mk_tag_id(tag);
-        let score: Option<i32> = query_scalar("select score from tags where tag_id = ?1")
-            .bind(&id)
-            .fetch_optional(&mut tx)
-            .await?;
+    for tag in resolved.iter() {
+        // a tag someone else made, eg, on a device without our author, is used rather than doubled
+        let existing = get_tag(&mut tx, identity, tag).await?;
+        let id = match existing.as_ref() {
+            Some(existing) => existing.id(),
+            None => mk_tag_id(identity, tag),
+        };
 
-        if let Some(oscore) = score {
-            let new_score = oscore + 1;
+        if let Some(existing) = existing {
+            let new_score = existing.get_score() + 1;
             let _ = query(r#"UPDATE tags SET score = ?1 WHERE tag_id = ?2"#)
                 .bind(new_score)
                 .bind(&id)
                 .execute(&mut tx)
                 .await?;
         } else {
-            let new_tag = models::Tag::new(tag.clone(), id, dev_id, Some(jot.creation_date), 1);
+            let new_tag = models::Tag::new(
+                tag.clone(),
+                id,
+                origin.device_id,
+                origin.creation_date,
+                1,
+                origin.author_id,
+            );
             let _ = new_tag.as_insert().execute(&mut tx).await?;
         };
 
         // now the mapping
         let mapping_id = mk_mapping_id(&jot_id, &id);
-        let mapping = models::Mapping::new(mapping_id, id, jot_id, Some(jot.creation_date));
+        let mapping = models::Mapping::new(mapping_id, id, jot_id, origin.creation_date);
         let _ = mapping.as_insert().execute(&mut tx).await?;
     }
 
     tx.commit().await?;
 
-    Ok(())
+    Ok(jot_id)
+}
+
+/// Store arbitrary bytes, like an image or a PDF, as a jot of the given MIME type that is attached
+/// to the jot `to`, written by `identity`. Returns the ID of the new jot.
+pub async fn insert_attachment(
+    pool: &SqlitePool,
+    identity: &Identity,
+    to: Uuid,
+    content: &[u8],
+    mime_type: &str,
+    creation_date: StarDate,
+) -> Result<Uuid> {
+    if !jot_exists(pool, to).await? {
+        return Err(Error::NotFound(format!(
+            "no jot with ID {}",
+            to.to_simple()
+        )));
+    }
+
+    let origin = JotOrigin::local(identity, creation_date);
+
+    let mut tx = pool.begin().await?;
+
+    let jot_id = insert_jot_row(
+        &mut tx,
+        mk_content_id(identity, content, &creation_date),
+        content.to_vec(),
+        mime_type.to_owned(),
+        origin.clone(),
+    )
+    .await?;
+
+    let link = models::Link::new(
+        mk_link_id(&jot_id, &to, LinkType::Attachment),
+        jot_id,
+        to,
+        LinkType::Attachment,
+        origin.device_id,
+        origin.creation_date,
+    );
+    let _ = link.as_insert().execute(&mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(jot_id)
+}
+
+// Insert the row for a new jot, refusing if it was deleted, and giving it a fresh ID (with the
+// deterministic one as its `dup_id`) if there's already a jot with that ID.
+async fn insert_jot_row(
+    tx: &mut Transaction<'_, Sqlite>,
+    mut jot_id: Uuid,
+    content: Vec<u8>,
+    mime_type: String,
+    mut origin: JotOrigin,
+) -> Result<Uuid> {
+    let tombstones: u32 = query_scalar("SELECT COUNT(*) FROM tombstones WHERE jot_id = ?1")
+        .bind(&jot_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if tombstones > 0 {
+        return Err(Error::Invalid(format!(
+            "jot {} has been deleted",
+            jot_id.to_simple()
+        )));
+    }
+
+    let jot_count: u32 = query_scalar(
+        r#"
+SELECT COUNT(*) FROM jots WHERE jot_id = ?1
+"#,
+    )
+    .bind(&jot_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if jot_count > 0 {
+        origin.dup_id = Some(jot_id);
+        jot_id = Uuid::new_v4();
+    };
+
+    let content = match sealing_key(&mut *tx).await? {
+        Some(key) => seal(&key, jot_id, &content)?,
+        None => content,
+    };
+
+    let new_jot = models::Jot::new(
+        jot_id,
+        origin.creation_date,
+        content,
+        mime_type,
+        origin.device_id,
+        origin.dup_id,
+        origin.author_id,
+    );
+
+    let _ = new_jot.as_insert().execute(&mut *tx).await?;
+
+    Ok(jot_id)
 }
 
-pub async fn get_jots(conn: &SqlitePool) -> Vec<Jot> {
-    match query_as(
+pub async fn get_jots(conn: &SqlitePool) -> Result<Vec<Jot>> {
+    let jots = query_as(
         r#"
 SELECT * FROM jots ORDER BY jot_creation_date DESC
 "#,
     )
     .fetch_all(conn)
-    .await
-    {
-        Ok(jots) => jots,
-        _ => panic!(),
+    .await?;
+
+    unsealed(jots)
+}
+
+/// The jots selected by `filter`, newest first.
+pub async fn get_jots_by_tags(conn: &SqlitePool, filter: &TagFilter) -> Result<Vec<Jot>> {
+    let filter = JotFilter {
+        tags: filter.clone(),
+        ..Default::default()
+    };
+
+    get_jots_filtered(conn, &filter).await
+}
+
+/// The jots selected by `filter`, newest first.
+pub async fn get_jots_filtered(conn: &SqlitePool, filter: &JotFilter) -> Result<Vec<Jot>> {
+    let (sql, args) = select_jots(conn, "jots.*", filter, None, None).await?;
+    let jots = query_as_with(&sql, args).fetch_all(conn).await?;
+
+    unsealed(jots)
+}
+
+/// At most `limit` of the jots selected by `filter`, newest first, starting just after `after`.
+/// Pass the cursor of the last jot of one page to get the next, until a page comes back short.
+pub async fn get_jot_page(
+    conn: &SqlitePool,
+    filter: &JotFilter,
+    after: Option<&JotCursor>,
+    limit: u32,
+) -> Result<Vec<Jot>> {
+    let (sql, args) = select_jots(conn, "jots.*", filter, after, Some(limit)).await?;
+    let jots = query_as_with(&sql, args).fetch_all(conn).await?;
+
+    unsealed(jots)
+}
+
+/// Like `get_jot_page`, but without the jots' content, for listing jots cheaply; use `get_jot` for
+/// the whole of one.
+pub async fn list_jots(
+    conn: &SqlitePool,
+    filter: &JotFilter,
+    after: Option<&JotCursor>,
+    limit: u32,
+) -> Result<Vec<JotSummary>> {
+    // sealed content has to be read in full to be unsealed, and is cut down afterwards
+    let columns = r#"
+jots.jot_id, jots.jot_creation_date, jots.jot_content_type, jots.device_id,
+length(jots.jot_content) AS jot_size,
+CASE
+    WHEN substr(jots.jot_content, 1, 6) = x'006a656e6301' THEN jots.jot_content
+    WHEN jots.jot_content_type LIKE 'text/%' THEN substr(jots.jot_content, 1, 240)
+    ELSE x''
+END AS preview
+"#;
+    let (sql, args) = select_jots(conn, columns, filter, after, Some(limit)).await?;
+    let mut summaries: Vec<JotSummary> = query_as_with(&sql, args).fetch_all(conn).await?;
+    for summary in summaries.iter_mut() {
+        summary.unseal()?;
+    }
+
+    Ok(summaries)
+}
+
+// The query for `columns` of the jots selected by `filter`, newest first with ties broken by ID so
+// that cursors are exact, and its arguments.
+async fn select_jots(
+    conn: &SqlitePool,
+    columns: &str,
+    filter: &JotFilter,
+    after: Option<&JotCursor>,
+    limit: Option<u32>,
+) -> Result<(String, SqliteArguments<'static>)> {
+    let mut conds = Vec::new();
+    let mut args = SqliteArguments::default();
+    push_filter(conn, filter, &mut conds, &mut args).await?;
+    if let Some(after) = after {
+        after.push_conditions(&mut conds, &mut args);
+    }
+
+    let mut sql = format!("SELECT {} FROM jots", columns);
+    if !conds.is_empty() {
+        sql = format!("{} WHERE {}", sql, conds.join(" AND "));
     }
+    sql.push_str(" ORDER BY jots.jot_creation_date DESC, jots.jot_id DESC");
+    if let Some(limit) = limit {
+        sql = format!("{} LIMIT {}", sql, limit);
+    }
+
+    Ok((sql, args))
+}
+
+// Add the conditions for `filter`, looking up the tags its patterns could match if it has any.
+async fn push_filter(
+    conn: &SqlitePool,
+    filter: &JotFilter,
+    conds: &mut Vec<String>,
+    args: &mut SqliteArguments<'static>,
+) -> Result<()> {
+    let known = if filter.tags.has_globs() {
+        get_tags(conn).await?
+    } else {
+        Vec::new()
+    };
+    filter.push_conditions(conds, args, &known);
+
+    Ok(())
 }
 
-pub async fn get_jot(conn: &SqlitePool, id: Uuid) -> Jot {
-    match query_as(
+/// The jot with the given ID, or `Error::NotFound` if there isn't one.
+pub async fn get_jot(conn: &SqlitePool, id: Uuid) -> Result<Jot> {
+    let jot = query_as(
         r#"
 SELECT * FROM jots WHERE jot_id = ?1
 "#,
     )
     .bind(&id)
-    .fetch_one(conn)
-    .await
+    .fetch_optional(conn)
+    .await?;
+
+    let mut jot: Jot =
+        jot.ok_or_else(|| Error::NotFound(format!("no jot with ID {}", id.to_simple())))?;
+    jot.unseal()?;
+
+    Ok(jot)
+}
+
+/// Replace the content of a textual jot, keeping its ID, and record the change in the jot's
+/// revision history. The first edit also records the original content, so the history always
+/// ends with the current content. Returns false if the content was unchanged.
+pub async fn edit_jot(pool: &SqlitePool, id: Uuid, content: &str) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+
+    let mut jot: Jot = match query_as("SELECT * FROM jots WHERE jot_id = ?1")
+        .bind(&id)
+        .fetch_optional(&mut tx)
+        .await?
     {
-        Ok(jot) => jot,
-        _ => panic!(),
+        Some(jot) => jot,
+        None => {
+            return Err(Error::NotFound(format!(
+                "no jot with ID {}",
+                id.to_simple()
+            )))
+        }
+    };
+
+    jot.unseal()?;
+    let old = jot.content();
+    if !old.mime_type.starts_with("text/") {
+        return Err(Error::Invalid(format!(
+            "can't edit a jot of type {}",
+            old.mime_type
+        )));
+    }
+    if old.bytes == content.as_bytes() {
+        return Ok(false);
+    }
+
+    // in an encrypted journal, every version is sealed
+    let key = sealing_key(&mut tx).await?;
+    let seal_content = |bytes: &[u8]| match key.as_ref() {
+        Some(key) => seal(key, id, bytes),
+        None => Ok(bytes.to_vec()),
+    };
+
+    let revisions: u32 = query_scalar("SELECT COUNT(*) FROM jot_revisions WHERE jot_id = ?1")
+        .bind(&id)
+        .fetch_one(&mut tx)
+        .await?;
+
+    if revisions == 0 {
+        let date = jot.created().unwrap_or_else(chrono::Utc::now);
+        let original = models::Revision::new(
+            mk_revision_id(&id, old.bytes, &date),
+            id,
+            date,
+            seal_content(old.bytes)?,
+            old.mime_type.to_owned(),
+            jot.device(),
+        );
+        let _ = original.as_insert().execute(&mut tx).await?;
+    }
+
+    let date = chrono::Utc::now();
+    let revision = models::Revision::new(
+        mk_revision_id(&id, content.as_bytes(), &date),
+        id,
+        date,
+        seal_content(content.as_bytes())?,
+        old.mime_type.to_owned(),
+        get_device_id()?,
+    );
+    let _ = revision.as_insert().execute(&mut tx).await?;
+
+    let _ = query("UPDATE jots SET jot_content = ?1 WHERE jot_id = ?2")
+        .bind(seal_content(content.as_bytes())?)
+        .bind(&id)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Delete a jot, leaving a tombstone behind so that it stays deleted when merging with another
+/// device's database. The scores of its tags go down by one, and its tag mappings, links and
+/// revisions are deleted along with it.
+pub async fn delete_jot(pool: &SqlitePool, id: Uuid) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    let count: u32 = query_scalar("SELECT COUNT(*) FROM jots WHERE jot_id = ?1")
+        .bind(&id)
+        .fetch_one(&mut tx)
+        .await?;
+    if count == 0 {
+        return Err(Error::NotFound(format!(
+            "no jot with ID {}",
+            id.to_simple()
+        )));
+    }
+
+    let _ = query(
+        r#"
+UPDATE tags SET score = score - 1
+WHERE score > 0 AND tag_id IN (SELECT tag_id FROM tag_map WHERE jot_id = ?1)
+"#,
+    )
+    .bind(&id)
+    .execute(&mut tx)
+    .await?;
+
+    let _ = query("DELETE FROM jots WHERE jot_id = ?1")
+        .bind(&id)
+        .execute(&mut tx)
+        .await?;
+
+    let tombstone = models::Tombstone::new(id, get_device_id()?, chrono::Utc::now());
+    let _ = tombstone.as_insert().execute(&mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn get_tombstones(conn: &SqlitePool) -> Result<Vec<Tombstone>> {
+    let tombstones = query_as(
+        r#"
+SELECT * FROM tombstones ORDER BY julianday(tombstone_date) DESC
+"#,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(tombstones)
+}
+
+/// Every recorded version of the jot's content, oldest first; empty if it was never edited.
+pub async fn get_revisions(conn: &SqlitePool, id: Uuid) -> Result<Vec<Revision>> {
+    let mut revisions: Vec<Revision> = query_as(
+        r#"
+SELECT * FROM jot_revisions WHERE jot_id = ?1 ORDER BY julianday(revision_date)
+"#,
+    )
+    .bind(&id)
+    .fetch_all(conn)
+    .await?;
+    for revision in revisions.iter_mut() {
+        revision.unseal()?;
+    }
+
+    Ok(revisions)
+}
+
+/// Find the textual jots matching `search`, best match first. Each whitespace-separated word in the
+/// search must appear in the jot; a word ending in `*` matches as a prefix. Sealed jots aren't in
+/// the full-text index, so an encrypted journal is searched by reading through every textual jot,
+/// newest first, for the words anywhere in it.
+pub async fn search_jots(pool: &SqlitePool, search: &str) -> Result<Vec<SearchHit>> {
+    search_jots_filtered(pool, search, &JotFilter::default(), None).await
+}
+
+/// Like `search_jots`, but only among the jots selected by `filter`, and stopping after the best
+/// `limit` of them if there's a limit.
+pub async fn search_jots_filtered(
+    pool: &SqlitePool,
+    search: &str,
+    filter: &JotFilter,
+    limit: Option<u32>,
+) -> Result<Vec<SearchHit>> {
+    if is_encrypted(pool).await? {
+        return search_sealed(pool, search, filter, limit).await;
+    }
+
+    let fts = fts_query(search);
+    if fts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conds = vec!["jots_fts MATCH ?".to_owned()];
+    let mut args = SqliteArguments::default();
+    args.add(fts);
+    push_filter(pool, filter, &mut conds, &mut args).await?;
+
+    let mut sql = format!(
+        r#"
+SELECT jots.*, snippet(jots_fts, 0, '**', '**', '...', 16) AS snippet
+FROM jots_fts JOIN jots ON jots.jot_id = jots_fts.jot_id
+WHERE {}
+ORDER BY jots_fts.rank
+"#,
+        conds.join(" AND ")
+    );
+    if let Some(limit) = limit {
+        sql = format!("{} LIMIT {}", sql, limit);
+    }
+    let hits = query_as_with(&sql, args).fetch_all(pool).await?;
+
+    Ok(hits)
+}
+
+// Read through the jots selected by `filter` a page at a time, newest first, unsealing them to
+// look for the words.
+async fn search_sealed(
+    pool: &SqlitePool,
+    search: &str,
+    filter: &JotFilter,
+    limit: Option<u32>,
+) -> Result<Vec<SearchHit>> {
+    const SEARCH_PAGE: u32 = 500;
+
+    let words: Vec<String> = search
+        .split_whitespace()
+        .map(|word| word.trim_end_matches('*').to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+    let limit = limit.map_or(usize::MAX, |l| l as usize);
+
+    let mut hits = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = get_jot_page(pool, filter, cursor.as_ref(), SEARCH_PAGE).await?;
+        let full = page.len() as u32 == SEARCH_PAGE;
+        cursor = page.last().map(|last| last.cursor());
+
+        for jot in page {
+            if hits.len() == limit {
+                return Ok(hits);
+            }
+            if !jot.content().mime_type.starts_with("text/") {
+                continue;
+            }
+
+            let text = jot.content().to_string();
+            let lower = text.to_lowercase();
+            if words.iter().all(|word| lower.contains(word.as_str())) {
+                let snippet = snippet(&text, &words);
+                hits.push(SearchHit { jot, snippet });
+            }
+        }
+
+        if !full {
+            return Ok(hits);
+        }
+    }
+}
+
+// About sixteen words of the text around the first match, with the matching words in bold, the way
+// the full-text index's `snippet()` does it.
+fn snippet(text: &str, words: &[String]) -> String {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let matches = |token: &str| {
+        let token = token.to_lowercase();
+        words.iter().any(|word| token.contains(word.as_str()))
+    };
+
+    let first = tokens.iter().position(|t| matches(t)).unwrap_or(0);
+    let start = first.saturating_sub(4);
+    let end = tokens.len().min(start + 16);
+
+    let mut snippet: Vec<String> = tokens[start..end]
+        .iter()
+        .map(|t| {
+            if matches(t) {
+                format!("**{}**", t)
+            } else {
+                t.to_string()
+            }
+        })
+        .collect();
+    if start > 0 {
+        snippet.insert(0, "...".to_owned());
+    }
+    if end < tokens.len() {
+        snippet.push("...".to_owned());
+    }
+
+    snippet.join(" ")
+}
+
+// Quote each word so that punctuation in the search (like the colon in "host:foo") isn't taken as
+// FTS5 query syntax.
+fn fts_query(search: &str) -> String {
+    search
+        .split_whitespace()
+        .map(|word| match word.strip_suffix('*') {
+            Some(w) => (w, "*"),
+            None => (word, ""),
+        })
+        .filter(|(word, _)| !word.is_empty())
+        .map(|(word, prefix)| format!("\"{}\"{}", word.replace('"', "\"\""), prefix))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub async fn jot_exists(conn: &SqlitePool, id: Uuid) -> Result<bool> {
+    let count: u32 = query_scalar("SELECT COUNT(*) FROM jots WHERE jot_id = ?1")
+        .bind(&id)
+        .fetch_one(conn)
+        .await?;
+
+    Ok(count > 0)
+}
+
+/// Record that `from` is a `link_type` of `to`, eg, that `from` is a reply to `to`. Linking the
+/// same pair the same way twice is a no-op.
+pub async fn insert_link(
+    pool: &SqlitePool,
+    from: Uuid,
+    to: Uuid,
+    link_type: LinkType,
+) -> Result<()> {
+    for id in [from, to].iter() {
+        if !jot_exists(pool, *id).await? {
+            return Err(Error::NotFound(format!(
+                "no jot with ID {}",
+                id.to_simple()
+            )));
+        }
+    }
+
+    let link_id = mk_link_id(&from, &to, link_type);
+    let link = models::Link::new(
+        link_id,
+        from,
+        to,
+        link_type,
+        get_device_id()?,
+        Some(chrono::Utc::now()),
+    );
+    let _ = link.as_insert().execute(pool).await?;
+
+    Ok(())
+}
+
+/// All links that start or end at the given jot.
+pub async fn get_links(conn: &SqlitePool, id: Uuid) -> Result<Vec<Link>> {
+    let links = query_as(
+        r#"
+SELECT * FROM jot_links WHERE from_jot = ?1 OR to_jot = ?1 ORDER BY link_date
+"#,
+    )
+    .bind(&id)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(links)
+}
+
+/// The jots whose parent is the given jot, newest first.
+pub async fn get_children(conn: &SqlitePool, id: Uuid) -> Result<Vec<Jot>> {
+    let jots = query_as(
+        r#"
+SELECT jots.* FROM jots JOIN jot_links ON jots.jot_id = jot_links.from_jot
+WHERE jot_links.to_jot = ?1 AND jot_links.link_type = 'parent'
+ORDER BY jot_creation_date DESC
+"#,
+    )
+    .bind(&id)
+    .fetch_all(conn)
+    .await?;
+
+    unsealed(jots)
+}
+
+/// The chain of parents above the given jot, starting with its immediate parent. A jot may have
+/// more than one parent, in which case all of them are walked.
+pub async fn get_ancestors(conn: &SqlitePool, id: Uuid) -> Result<Vec<Jot>> {
+    // the depth limit keeps a cycle of parent links from recursing forever
+    let jots = query_as(
+        r#"
+WITH RECURSIVE ancestors(jot_id, depth) AS (
+    SELECT to_jot, 1 FROM jot_links WHERE from_jot = ?1 AND link_type = 'parent'
+    UNION
+    SELECT jot_links.to_jot, ancestors.depth + 1 FROM jot_links
+    JOIN ancestors ON jot_links.from_jot = ancestors.jot_id
+    WHERE jot_links.link_type = 'parent' AND ancestors.depth < 1000
+)
+SELECT jots.* FROM jots JOIN ancestors ON jots.jot_id = ancestors.jot_id
+WHERE jots.jot_id != ?1
+GROUP BY jots.jot_id
+ORDER BY MIN(ancestors.depth)
+"#,
+    )
+    .bind(&id)
+    .fetch_all(conn)
+    .await?;
+
+    unsealed(jots)
+}
+
+// Swap sealed content for plain content, for jots read straight from the database.
+fn unsealed(mut jots: Vec<Jot>) -> Result<Vec<Jot>> {
+    for jot in jots.iter_mut() {
+        jot.unseal()?;
     }
+
+    Ok(jots)
 }
 
 pub fn parse_tags(tagline: &str) -> Vec<String> {
@@ -0,0 +1,282 @@
+use std::{fmt::Display, path::Path};
+
+use sqlx::{
+    query, query_as, query_scalar,
+    sqlite::{SqliteConnectOptions, SqliteConnection},
+    Connection, SqlitePool,
+};
+
+use super::{
+    create_temp_file, fold_aliased_tags, make_pool, remove_db_file, rescore_tags, seal_all,
+    sealing_key, unseal, Error, Result, Uuid,
+};
+
+/// What `merge_database` brought in from the other database.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MergeReport {
+    pub jots_added: u64,
+    pub tags_added: u64,
+    pub mappings_added: u64,
+    pub aliases_added: u64,
+    pub links_added: u64,
+    pub revisions_added: u64,
+    pub tombstones_added: u64,
+    /// Tags folded into the tag they had been renamed or merged into on one side or the other.
+    pub tags_folded: u64,
+    /// Local jots that the other database had deleted.
+    pub jots_deleted: u64,
+    /// Jots with the same ID in both databases but different content and no revision history to
+    /// explain the difference; the local version is kept. Sealed content is compared unsealed where
+    /// its key is unlocked, and as it's stored where it isn't.
+    pub collisions: Vec<Uuid>,
+}
+
+impl Display for MergeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "jots added:       {}", self.jots_added)?;
+        writeln!(f, "tags added:       {}", self.tags_added)?;
+        writeln!(f, "mappings added:   {}", self.mappings_added)?;
+        writeln!(f, "aliases added:    {}", self.aliases_added)?;
+        writeln!(f, "links added:      {}", self.links_added)?;
+        writeln!(f, "revisions added:  {}", self.revisions_added)?;
+        writeln!(f, "tombstones added: {}", self.tombstones_added)?;
+        writeln!(f, "tags folded:      {}", self.tags_folded)?;
+        writeln!(f, "jots deleted:     {}", self.jots_deleted)?;
+        if !self.collisions.is_empty() {
+            writeln!(
+                f,
+                "\n{} jot(s) differ between the databases; kept the local version of:",
+                self.collisions.len()
+            )?;
+            for id in self.collisions.iter() {
+                writeln!(f, "  {}", id.to_simple())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bring everything from the database at `other` into this one: jots, tags, mappings, aliases,
+/// links, revisions and tombstones are unioned by ID, jots deleted on either side stay deleted, edited
+/// jots end up with their most recent revision, and tag scores are recounted from the merged
+/// mappings. The other database is only read: a private copy of it is brought up to date and merged
+/// instead, and one with a newer schema than this joenal understands is refused.
+pub async fn merge_database(pool: &SqlitePool, other: &Path) -> Result<MergeReport> {
+    if !other.is_file() {
+        return Err(Error::NotFound(format!(
+            "no database at {}",
+            other.display()
+        )));
+    }
+
+    let (copy, file) = create_temp_file(".db")?;
+    drop(file);
+
+    let report = merge_copy(pool, other, &copy).await;
+
+    remove_db_file(&copy);
+
+    report
+}
+
+async fn merge_copy(pool: &SqlitePool, other: &Path, copy: &Path) -> Result<MergeReport> {
+    let copy = copy.to_string_lossy();
+
+    let opts = SqliteConnectOptions::new().filename(other).read_only(true);
+    let mut source = SqliteConnection::connect_with(&opts).await?;
+    let _ = query("VACUUM INTO ?1")
+        .bind(copy.as_ref())
+        .execute(&mut source)
+        .await?;
+    source.close().await?;
+
+    let mut conn = pool.acquire().await?;
+
+    // plain content coming into an encrypted journal is sealed first, so that none of it is ever
+    // stored or indexed unsealed; this needs the journal unlocked
+    let copy_pool = make_pool(&copy).await?;
+    if let Some(key) = sealing_key(&mut conn).await? {
+        let mut tx = copy_pool.begin().await?;
+        let _ = seal_all(&mut tx, &key).await?;
+        tx.commit().await?;
+    }
+    copy_pool.close().await;
+
+    let _ = query("ATTACH DATABASE ?1 AS other")
+        .bind(copy.as_ref())
+        .execute(&mut conn)
+        .await?;
+
+    let report = merge_attached(&mut conn).await;
+
+    let _ = query("DETACH DATABASE other").execute(&mut conn).await?;
+
+    report
+}
+
+async fn merge_attached(conn: &mut SqliteConnection) -> Result<MergeReport> {
+    let mut report = MergeReport::default();
+    let mut tx = conn.begin().await?;
+
+    // sealed content is copied as it is, so the keys it was sealed with come along too
+    let _ = query(
+        r#"
+INSERT OR IGNORE INTO main.journal_keys
+       (key_id, salt, kdf_params, key_check, device_id, key_date)
+SELECT key_id, salt, kdf_params, key_check, device_id, key_date FROM other.journal_keys
+"#,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    report.tombstones_added = query(
+        r#"
+INSERT OR IGNORE INTO main.tombstones (jot_id, device_id, tombstone_date)
+SELECT jot_id, device_id, tombstone_date FROM other.tombstones
+"#,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    // note the jots that differ before anything changes, so we can tell edits from collisions
+    let stored: Vec<(Uuid, Vec<u8>, Vec<u8>)> = query_as(
+        r#"
+SELECT m.jot_id, m.jot_content, o.jot_content FROM main.jots m JOIN other.jots o
+ON m.jot_id = o.jot_id
+WHERE m.jot_content != o.jot_content OR m.jot_content_type != o.jot_content_type
+"#,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+    let differing: Vec<Uuid> = stored
+        .into_iter()
+        .filter(|(id, ours, theirs)| readable(*id, ours) != readable(*id, theirs))
+        .map(|(id, _, _)| id)
+        .collect();
+
+    report.jots_added = query(
+        r#"
+INSERT INTO main.jots
+       (jot_id, jot_creation_date, jot_content, jot_content_type, device_id, dup_id, author_id)
+SELECT jot_id, jot_creation_date, jot_content, jot_content_type, device_id, dup_id, author_id
+FROM other.jots
+WHERE jot_id NOT IN (SELECT jot_id FROM main.jots)
+  AND jot_id NOT IN (SELECT jot_id FROM main.tombstones)
+"#,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    report.tags_added = query(
+        r#"
+INSERT OR IGNORE INTO main.tags
+       (tag_id, tag_creation_date, tag_text, device_id, score, tag_namespace, tag_value, author_id)
+SELECT tag_id, tag_creation_date, tag_text, device_id, 0, tag_namespace, tag_value, author_id
+FROM other.tags
+"#,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    report.mappings_added = query(
+        r#"
+INSERT OR IGNORE INTO main.tag_map (mapping_id, tag_id, jot_id, mapping_date)
+SELECT mapping_id, tag_id, jot_id, mapping_date FROM other.tag_map
+WHERE jot_id IN (SELECT jot_id FROM main.jots)
+"#,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    report.aliases_added = query(
+        r#"
+INSERT OR IGNORE INTO main.tag_aliases (alias_id, alias_text, tag_id, device_id, alias_date)
+SELECT alias_id, alias_text, tag_id, device_id, alias_date FROM other.tag_aliases
+WHERE tag_id IN (SELECT tag_id FROM main.tags)
+"#,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    // a tag renamed or merged away on one side is an alias now, so its jots follow it from the other
+    report.tags_folded = fold_aliased_tags(&mut tx).await?;
+
+    report.links_added = query(
+        r#"
+INSERT OR IGNORE INTO main.jot_links (link_id, from_jot, to_jot, link_type, device_id, link_date)
+SELECT link_id, from_jot, to_jot, link_type, device_id, link_date FROM other.jot_links
+WHERE from_jot IN (SELECT jot_id FROM main.jots) AND to_jot IN (SELECT jot_id FROM main.jots)
+"#,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    report.revisions_added = query(
+        r#"
+INSERT OR IGNORE INTO main.jot_revisions
+       (revision_id, jot_id, revision_date, jot_content, jot_content_type, device_id)
+SELECT revision_id, jot_id, revision_date, jot_content, jot_content_type, device_id
+FROM other.jot_revisions
+WHERE jot_id IN (SELECT jot_id FROM main.jots)
+"#,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    // an edited jot takes the content of its latest revision, wherever that was made
+    let _ = query(
+        r#"
+UPDATE main.jots SET
+    jot_content = (SELECT r.jot_content FROM main.jot_revisions r WHERE r.jot_id = jots.jot_id
+                   ORDER BY julianday(r.revision_date) DESC LIMIT 1),
+    jot_content_type = (SELECT r.jot_content_type FROM main.jot_revisions r
+                        WHERE r.jot_id = jots.jot_id
+                        ORDER BY julianday(r.revision_date) DESC LIMIT 1)
+WHERE jot_id IN (SELECT jot_id FROM main.jot_revisions)
+"#,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    for id in differing {
+        let revisions: u32 =
+            query_scalar("SELECT COUNT(*) FROM main.jot_revisions WHERE jot_id = ?1")
+                .bind(&id)
+                .fetch_one(&mut tx)
+                .await?;
+        if revisions == 0 {
+            report.collisions.push(id);
+        }
+    }
+
+    report.jots_deleted = query(
+        r#"
+DELETE FROM main.jots WHERE jot_id IN (SELECT jot_id FROM main.tombstones)
+"#,
+    )
+    .execute(&mut tx)
+    .await?
+    .rows_affected();
+
+    rescore_tags(&mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+// Content as it reads: unsealed if it's sealed under a key that's unlocked, so that the same text
+// sealed under different keys compares equal, or else as it's stored.
+fn readable(jot_id: Uuid, content: &[u8]) -> Vec<u8> {
+    match unseal(jot_id, content) {
+        Ok(Some(plain)) => plain,
+        _ => content.to_vec(),
+    }
+}
@@ -1,25 +1,77 @@
 use std::{
     cmp::{Eq, PartialEq},
     fmt::Display,
+    str::FromStr,
 };
 
-use sqlx::{query::Query, sqlite::SqliteArguments, FromRow, Sqlite};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    query::Query,
+    sqlite::{SqliteArguments, SqliteRow},
+    FromRow, Row, Sqlite,
+};
 
-use super::{Labelable, StarDate, Uuid};
+use super::{split_tag, unseal, JotCursor, Labelable, StarDate, Uuid};
 
 pub struct Content<'jot> {
     pub bytes: &'jot [u8],
     pub mime_type: &'jot str,
 }
 
-#[derive(Clone, FromRow, Debug)]
+impl<'jot> Content<'jot> {
+    /// The content as a string, if it's text and valid UTF-8.
+    pub fn text(&self) -> Option<&'jot str> {
+        if self.mime_type.starts_with("text/") {
+            std::str::from_utf8(self.bytes).ok()
+        } else {
+            None
+        }
+    }
+
+    /// The file name an attachment was stored with, if any.
+    pub fn name(&self) -> Option<String> {
+        attachment_name(self.mime_type)
+    }
+
+    /// A stand-in for content that can't be shown as text, like "[image/png: cat.png, 1234 bytes]".
+    pub fn placeholder(&self) -> String {
+        placeholder(self.mime_type, self.bytes.len())
+    }
+}
+
+fn attachment_name(mime_type: &str) -> Option<String> {
+    let mime: mime::Mime = mime_type.parse().ok()?;
+    mime.get_param("name").map(|n| n.to_string())
+}
+
+fn placeholder(mime_type: &str, size: usize) -> String {
+    let essence = mime_type.split(';').next().unwrap_or_default().trim();
+    match attachment_name(mime_type) {
+        Some(name) => format!("[{}: {}, {} bytes]", essence, name, size),
+        None => format!("[{}, {} bytes]", essence, size),
+    }
+}
+
+impl Display for Content<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.text() {
+            Some(text) => write!(f, "{}", text),
+            None => write!(f, "{}", self.placeholder()),
+        }
+    }
+}
+
+#[derive(Clone, FromRow, Debug, Serialize, Deserialize)]
 pub struct Jot {
     jot_id: Uuid,
     jot_creation_date: Option<StarDate>,
+    #[serde(with = "base64_bytes")]
     jot_content: Vec<u8>,
     jot_content_type: String,
     device_id: Uuid,
     dup_id: Option<Uuid>,
+    #[serde(default)]
+    author_id: Option<Uuid>,
 }
 
 impl Jot {
@@ -30,6 +82,7 @@ impl Jot {
         jot_content_type: String,
         device_id: Uuid,
         dup_id: Option<Uuid>,
+        author_id: Option<Uuid>,
     ) -> Self {
         Jot {
             jot_id,
@@ -38,12 +91,13 @@ impl Jot {
             jot_content_type,
             device_id,
             dup_id,
+            author_id,
         }
     }
 
     pub fn as_insert(&self) -> Query<'static, Sqlite, SqliteArguments<'static>> {
         sqlx::query(r#"
-INSERT INTO jots (jot_id, jot_creation_date, jot_content, jot_content_type, device_id, dup_id) VALUES (?, ?, ?, ?, ?, ?)
+INSERT INTO jots (jot_id, jot_creation_date, jot_content, jot_content_type, device_id, dup_id, author_id) VALUES (?, ?, ?, ?, ?, ?, ?)
 "#
         )
             .bind(self.jot_id)
@@ -52,6 +106,7 @@ INSERT INTO jots (jot_id, jot_creation_date, jot_content, jot_content_type, devi
             .bind(self.jot_content_type.clone())
             .bind(self.device_id)
             .bind(self.dup_id)
+            .bind(self.author_id)
     }
 
     pub fn created(&self) -> Option<StarDate> {
@@ -62,12 +117,42 @@ INSERT INTO jots (jot_id, jot_creation_date, jot_content, jot_content_type, devi
         self.jot_id
     }
 
+    pub fn device(&self) -> Uuid {
+        self.device_id
+    }
+
+    pub fn dup(&self) -> Option<Uuid> {
+        self.dup_id
+    }
+
+    /// Who wrote the jot, if it was written since jots had authors.
+    pub fn author(&self) -> Option<Uuid> {
+        self.author_id
+    }
+
     pub fn content(&self) -> Content {
         Content {
             bytes: &self.jot_content,
             mime_type: &self.jot_content_type,
         }
     }
+
+    /// The place in a listing just after this jot, to start the next page from.
+    pub fn cursor(&self) -> JotCursor {
+        JotCursor {
+            date: self.jot_creation_date,
+            id: self.jot_id,
+        }
+    }
+
+    // Swap sealed content for the plain content, which needs the journal to be unlocked.
+    pub(crate) fn unseal(&mut self) -> crate::Result<()> {
+        if let Some(plain) = unseal(self.jot_id, &self.jot_content)? {
+            self.jot_content = plain;
+        }
+
+        Ok(())
+    }
 }
 
 impl Labelable for Jot {
@@ -81,11 +166,8 @@ impl Labelable for Jot {
         let dlen = date.len();
         let date = &date[0..(10.min(dlen))];
 
-        let content = std::str::from_utf8(self.content().bytes)
-            .unwrap()
-            .replace("\n", " ");
-        let clen = content.len();
-        let text = &content[0..(length.min(clen))];
+        let content = self.content().to_string().replace("\n", " ");
+        let text: String = content.chars().take(length).collect();
 
         format!("{}: {}...", date, text)
     }
@@ -111,7 +193,7 @@ impl Display for Jot {
             "Jot: {}\nCreated: {}\n\n{}\n",
             &self.jot_id.to_simple(),
             date,
-            std::str::from_utf8(&self.jot_content).unwrap()
+            self.content()
         )
     }
 }
@@ -122,7 +204,328 @@ impl druid::Data for Jot {
     }
 }
 
+/// Everything about a jot but its content, which is cut down to a short preview, for listing many
+/// jots without loading them all into memory.
 #[derive(Clone, FromRow, Debug)]
+pub struct JotSummary {
+    jot_id: Uuid,
+    jot_creation_date: Option<StarDate>,
+    jot_content_type: String,
+    device_id: Uuid,
+    jot_size: i64,
+    // the first few bytes of the content if it's text, which may end partway through a character
+    preview: Vec<u8>,
+}
+
+impl JotSummary {
+    pub fn id(&self) -> Uuid {
+        self.jot_id
+    }
+
+    pub fn created(&self) -> Option<StarDate> {
+        self.jot_creation_date
+    }
+
+    pub fn device(&self) -> Uuid {
+        self.device_id
+    }
+
+    pub fn content_type(&self) -> &str {
+        &self.jot_content_type
+    }
+
+    /// The length of the content in bytes.
+    pub fn size(&self) -> usize {
+        self.jot_size as usize
+    }
+
+    /// The start of the content if it's text, or a placeholder like `Content::placeholder` if not.
+    pub fn preview(&self) -> String {
+        if self.jot_content_type.starts_with("text/") {
+            String::from_utf8_lossy(&self.preview)
+                .trim_end_matches('\u{fffd}')
+                .to_owned()
+        } else {
+            placeholder(&self.jot_content_type, self.size())
+        }
+    }
+
+    /// The place in a listing just after this jot, to start the next page from.
+    pub fn cursor(&self) -> JotCursor {
+        JotCursor {
+            date: self.jot_creation_date,
+            id: self.jot_id,
+        }
+    }
+
+    // Sealed content is listed in full, so that it can be unsealed here and then cut down.
+    pub(crate) fn unseal(&mut self) -> crate::Result<()> {
+        if let Some(mut plain) = unseal(self.jot_id, &self.preview)? {
+            self.jot_size = plain.len() as i64;
+            if self.jot_content_type.starts_with("text/") {
+                plain.truncate(240);
+            } else {
+                plain.clear();
+            }
+            self.preview = plain;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&Jot> for JotSummary {
+    fn from(jot: &Jot) -> Self {
+        let mut preview = if jot.jot_content_type.starts_with("text/") {
+            jot.jot_content.clone()
+        } else {
+            Vec::new()
+        };
+        preview.truncate(240);
+
+        JotSummary {
+            jot_id: jot.jot_id,
+            jot_creation_date: jot.jot_creation_date,
+            jot_content_type: jot.jot_content_type.clone(),
+            device_id: jot.device_id,
+            jot_size: jot.jot_content.len() as i64,
+            preview,
+        }
+    }
+}
+
+impl Labelable for JotSummary {
+    fn short_label(&self, length: usize) -> String {
+        let date = match self.created() {
+            Some(d) => d.to_rfc3339()[..10].to_owned(),
+            None => "<no date>".to_string(),
+        };
+
+        let preview = self.preview().replace("\n", " ");
+        let text: String = preview.chars().take(length).collect();
+
+        format!("{}: {}...", date, text)
+    }
+}
+
+impl PartialEq for JotSummary {
+    fn eq(&self, other: &Self) -> bool {
+        self.jot_id == other.jot_id
+    }
+}
+
+/// One version of an edited jot's content, with the device that wrote it and when.
+#[derive(Clone, FromRow, Debug, Serialize, Deserialize)]
+pub struct Revision {
+    revision_id: Uuid,
+    jot_id: Uuid,
+    revision_date: StarDate,
+    #[serde(with = "base64_bytes")]
+    jot_content: Vec<u8>,
+    jot_content_type: String,
+    device_id: Uuid,
+}
+
+impl Revision {
+    pub fn new(
+        revision_id: Uuid,
+        jot_id: Uuid,
+        revision_date: StarDate,
+        jot_content: Vec<u8>,
+        jot_content_type: String,
+        device_id: Uuid,
+    ) -> Self {
+        Revision {
+            revision_id,
+            jot_id,
+            revision_date,
+            jot_content,
+            jot_content_type,
+            device_id,
+        }
+    }
+
+    pub fn date(&self) -> StarDate {
+        self.revision_date
+    }
+
+    pub fn device(&self) -> Uuid {
+        self.device_id
+    }
+
+    pub fn content(&self) -> Content {
+        Content {
+            bytes: &self.jot_content,
+            mime_type: &self.jot_content_type,
+        }
+    }
+
+    // Swap sealed content for the plain content, as for a jot.
+    pub(crate) fn unseal(&mut self) -> crate::Result<()> {
+        if let Some(plain) = unseal(self.jot_id, &self.jot_content)? {
+            self.jot_content = plain;
+        }
+
+        Ok(())
+    }
+
+    pub fn as_insert(&self) -> Query<'static, Sqlite, SqliteArguments<'static>> {
+        sqlx::query(r#"
+INSERT OR IGNORE INTO jot_revisions (revision_id, jot_id, revision_date, jot_content, jot_content_type, device_id) VALUES (?, ?, ?, ?, ?, ?)
+"#
+        )
+            .bind(self.revision_id)
+            .bind(self.jot_id)
+            .bind(self.revision_date)
+            .bind(self.jot_content.clone())
+            .bind(self.jot_content_type.clone())
+            .bind(self.device_id)
+    }
+}
+
+/// A key that jot content can be sealed with: not the key itself, but how to derive it from the
+/// journal's passphrase, and a known value sealed under it to check the passphrase against.
+#[derive(Clone, FromRow, Debug, Serialize, Deserialize)]
+pub struct JournalKey {
+    key_id: Uuid,
+    #[serde(with = "base64_bytes")]
+    salt: Vec<u8>,
+    kdf_params: String,
+    #[serde(with = "base64_bytes")]
+    key_check: Vec<u8>,
+    device_id: Uuid,
+    key_date: StarDate,
+}
+
+impl JournalKey {
+    pub fn new(
+        key_id: Uuid,
+        salt: Vec<u8>,
+        kdf_params: String,
+        key_check: Vec<u8>,
+        device_id: Uuid,
+        key_date: StarDate,
+    ) -> Self {
+        JournalKey {
+            key_id,
+            salt,
+            kdf_params,
+            key_check,
+            device_id,
+            key_date,
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.key_id
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn kdf_params(&self) -> &str {
+        &self.kdf_params
+    }
+
+    pub fn key_check(&self) -> &[u8] {
+        &self.key_check
+    }
+
+    pub fn as_insert(&self) -> Query<'static, Sqlite, SqliteArguments<'static>> {
+        sqlx::query(r#"
+INSERT OR IGNORE INTO journal_keys (key_id, salt, kdf_params, key_check, device_id, key_date) VALUES (?, ?, ?, ?, ?, ?)
+"#
+        )
+            .bind(self.key_id)
+            .bind(self.salt.clone())
+            .bind(self.kdf_params.clone())
+            .bind(self.key_check.clone())
+            .bind(self.device_id)
+            .bind(self.key_date)
+    }
+}
+
+/// The record left behind by a deleted jot.
+#[derive(Clone, FromRow, Debug, Serialize, Deserialize)]
+pub struct Tombstone {
+    jot_id: Uuid,
+    device_id: Uuid,
+    tombstone_date: StarDate,
+}
+
+impl Tombstone {
+    pub fn new(jot_id: Uuid, device_id: Uuid, tombstone_date: StarDate) -> Self {
+        Tombstone {
+            jot_id,
+            device_id,
+            tombstone_date,
+        }
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.jot_id
+    }
+
+    pub fn as_insert(&self) -> Query<'static, Sqlite, SqliteArguments<'static>> {
+        sqlx::query(
+            r#"
+INSERT OR IGNORE INTO tombstones (jot_id, device_id, tombstone_date) VALUES (?, ?, ?)
+"#,
+        )
+        .bind(self.jot_id)
+        .bind(self.device_id)
+        .bind(self.tombstone_date)
+    }
+}
+
+impl Display for Tombstone {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} deleted {} by device {}",
+            self.jot_id.to_simple(),
+            self.tombstone_date.to_rfc3339(),
+            self.device_id.to_simple()
+        )
+    }
+}
+
+/// A jot matched by a full-text search, along with an excerpt of its content in which the matching
+/// terms are wrapped in `**`.
+#[derive(Clone, Debug)]
+pub struct SearchHit {
+    pub jot: Jot,
+    pub snippet: String,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for SearchHit {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(SearchHit {
+            jot: Jot::from_row(row)?,
+            snippet: row.try_get("snippet")?,
+        })
+    }
+}
+
+impl Display for SearchHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let date = match self.jot.jot_creation_date {
+            Some(ref d) => d.to_rfc3339(),
+            None => "No date".to_owned(),
+        };
+
+        write!(
+            f,
+            "Jot: {}\nCreated: {}\n\n{}\n",
+            &self.jot.jot_id.to_simple(),
+            date,
+            self.snippet.replace("\n", " ")
+        )
+    }
+}
+
+#[derive(Clone, FromRow, Debug, Serialize, Deserialize)]
 pub struct Mapping {
     mapping_id: Uuid,
     tag_id: Uuid,
@@ -149,6 +552,18 @@ impl Mapping {
         sqlx::query(
             r#"
 INSERT INTO tag_map (mapping_id, tag_id, jot_id, mapping_date) VALUES (?, ?, ?, ?)
+"#,
+        )
+        .bind(self.mapping_id)
+        .bind(self.tag_id)
+        .bind(self.jot_id)
+        .bind(self.mapping_date)
+    }
+
+    pub fn as_insert_or_ignore(&self) -> Query<'static, Sqlite, SqliteArguments<'static>> {
+        sqlx::query(
+            r#"
+INSERT OR IGNORE INTO tag_map (mapping_id, tag_id, jot_id, mapping_date) VALUES (?, ?, ?, ?)
 "#,
         )
         .bind(self.mapping_id)
@@ -158,13 +573,17 @@ INSERT INTO tag_map (mapping_id, tag_id, jot_id, mapping_date) VALUES (?, ?, ?,
     }
 }
 
-#[derive(Clone, FromRow, Debug)]
+#[derive(Clone, FromRow, Debug, Serialize, Deserialize)]
 pub struct Tag {
     tag_id: Uuid,
     tag_creation_date: Option<StarDate>,
     tag_text: String,
     device_id: Uuid,
     score: i32,
+    tag_namespace: Option<String>,
+    tag_value: String,
+    #[serde(default)]
+    author_id: Option<Uuid>,
 }
 
 impl Tag {
@@ -174,13 +593,21 @@ impl Tag {
         device_id: Uuid,
         tag_creation_date: Option<StarDate>,
         score: i32,
+        author_id: Option<Uuid>,
     ) -> Self {
+        let (namespace, value) = split_tag(&tag_text);
+        let tag_namespace = namespace.map(|n| n.to_owned());
+        let tag_value = value.to_owned();
+
         Tag {
             tag_id,
             tag_creation_date,
             tag_text,
             device_id,
             score,
+            tag_namespace,
+            tag_value,
+            author_id,
         }
     }
 
@@ -188,22 +615,234 @@ impl Tag {
         self.score
     }
 
+    pub fn id(&self) -> Uuid {
+        self.tag_id
+    }
+
+    pub fn text(&self) -> &str {
+        &self.tag_text
+    }
+
+    /// The part of a tag like "host:laptop" before the colon.
+    pub fn namespace(&self) -> Option<&str> {
+        self.tag_namespace.as_deref()
+    }
+
+    /// The part of a tag like "host:laptop" after the colon, or the whole text of a tag without a
+    /// namespace.
+    pub fn value(&self) -> &str {
+        &self.tag_value
+    }
+
+    pub fn device(&self) -> Uuid {
+        self.device_id
+    }
+
+    /// Who made the tag, if it was made since tags had authors.
+    pub fn author(&self) -> Option<Uuid> {
+        self.author_id
+    }
+
+    pub fn created(&self) -> Option<StarDate> {
+        self.tag_creation_date
+    }
+
+    pub fn as_insert(&self) -> Query<'static, Sqlite, SqliteArguments<'static>> {
+        sqlx::query(r#"
+INSERT INTO tags (tag_id, tag_creation_date, tag_text, device_id, score, tag_namespace, tag_value, author_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+"#
+        )
+            .bind(self.tag_id)
+            .bind(self.tag_creation_date)
+            .bind(self.tag_text.clone())
+            .bind(self.device_id)
+            .bind(self.score)
+            .bind(self.tag_namespace.clone())
+            .bind(self.tag_value.clone())
+            .bind(self.author_id)
+    }
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.tag_text, self.score)
+    }
+}
+
+/// Another name for a tag, resolved to the tag itself when jots are inserted.
+#[derive(Clone, FromRow, Debug, Serialize, Deserialize)]
+pub struct TagAlias {
+    alias_id: Uuid,
+    alias_text: String,
+    tag_id: Uuid,
+    device_id: Uuid,
+    alias_date: Option<StarDate>,
+}
+
+impl TagAlias {
+    pub fn new(
+        alias_text: String,
+        alias_id: Uuid,
+        tag_id: Uuid,
+        device_id: Uuid,
+        alias_date: Option<StarDate>,
+    ) -> Self {
+        TagAlias {
+            alias_id,
+            alias_text,
+            tag_id,
+            device_id,
+            alias_date,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.alias_text
+    }
+
+    pub fn tag_id(&self) -> Uuid {
+        self.tag_id
+    }
+
     pub fn as_insert(&self) -> Query<'static, Sqlite, SqliteArguments<'static>> {
         sqlx::query(
             r#"
-INSERT INTO tags (tag_id, tag_creation_date, tag_text, device_id, score) VALUES (?, ?, ?, ?, ?)
+INSERT INTO tag_aliases (alias_id, alias_text, tag_id, device_id, alias_date) VALUES (?, ?, ?, ?, ?)
 "#,
         )
+        .bind(self.alias_id)
+        .bind(self.alias_text.clone())
         .bind(self.tag_id)
-        .bind(self.tag_creation_date)
-        .bind(self.tag_text.clone())
         .bind(self.device_id)
-        .bind(self.score)
+        .bind(self.alias_date)
     }
 }
 
-impl Display for Tag {
+/// The kind of relationship a `Link` records between two jots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkType {
+    Parent,
+    Reply,
+    SeeAlso,
+    Supersedes,
+    Attachment,
+}
+
+impl LinkType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkType::Parent => "parent",
+            LinkType::Reply => "reply",
+            LinkType::SeeAlso => "see-also",
+            LinkType::Supersedes => "supersedes",
+            LinkType::Attachment => "attachment",
+        }
+    }
+}
+
+impl FromStr for LinkType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parent" => Ok(LinkType::Parent),
+            "reply" => Ok(LinkType::Reply),
+            "see-also" => Ok(LinkType::SeeAlso),
+            "supersedes" => Ok(LinkType::Supersedes),
+            "attachment" => Ok(LinkType::Attachment),
+            _ => Err(format!("unknown link type '{}'", s)),
+        }
+    }
+}
+
+impl Display for LinkType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}: {}", self.tag_text, self.score)
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A directed edge between two jots, read as "`from_jot` is a `link_type` of `to_jot`"; a child
+/// jot has a `Parent` link pointing at its parent.
+#[derive(Clone, FromRow, Debug, Serialize, Deserialize)]
+pub struct Link {
+    link_id: Uuid,
+    from_jot: Uuid,
+    to_jot: Uuid,
+    link_type: LinkType,
+    device_id: Uuid,
+    link_date: Option<StarDate>,
+}
+
+impl Link {
+    pub fn new(
+        link_id: Uuid,
+        from_jot: Uuid,
+        to_jot: Uuid,
+        link_type: LinkType,
+        device_id: Uuid,
+        link_date: Option<StarDate>,
+    ) -> Self {
+        Link {
+            link_id,
+            from_jot,
+            to_jot,
+            link_type,
+            device_id,
+            link_date,
+        }
+    }
+
+    pub fn from_jot(&self) -> Uuid {
+        self.from_jot
+    }
+
+    pub fn to_jot(&self) -> Uuid {
+        self.to_jot
+    }
+
+    pub fn link_type(&self) -> LinkType {
+        self.link_type
+    }
+
+    pub fn as_insert(&self) -> Query<'static, Sqlite, SqliteArguments<'static>> {
+        sqlx::query(
+            r#"
+INSERT OR IGNORE INTO jot_links (link_id, from_jot, to_jot, link_type, device_id, link_date) VALUES (?, ?, ?, ?, ?, ?)
+"#,
+        )
+        .bind(self.link_id)
+        .bind(self.from_jot)
+        .bind(self.to_jot)
+        .bind(self.link_type)
+        .bind(self.device_id)
+        .bind(self.link_date)
+    }
+}
+
+impl Display for Link {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.from_jot.to_simple(),
+            self.link_type,
+            self.to_jot.to_simple()
+        )
+    }
+}
+
+// Content is serialized as base64 so that binary jots survive being written out as JSON.
+mod base64_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::decode(encoded).map_err(serde::de::Error::custom)
     }
 }
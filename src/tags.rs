@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use sqlx::{query, query_as, query_scalar, sqlite::SqliteConnection, SqlitePool};
+
+use super::{
+    mk_mapping_id, mk_tag_id, models, placeholders, Error, Identity, Result, Tag, TagAlias, Uuid,
+};
+
+// how many jot IDs to look up at once, well under SQLite's limit on bound parameters
+const ID_BATCH: usize = 500;
+
+/// Every tag, highest score first.
+pub async fn get_tags(conn: &SqlitePool) -> Result<Vec<Tag>> {
+    let tags = query_as(
+        r#"
+SELECT * FROM tags ORDER BY score DESC, tag_text
+"#,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(tags)
+}
+
+/// The text of every jot's tags, alphabetically, by jot ID.
+pub async fn get_jot_tags(conn: &SqlitePool) -> Result<HashMap<Uuid, Vec<String>>> {
+    let rows: Vec<(Uuid, String)> = query_as(
+        r#"
+SELECT tag_map.jot_id, tags.tag_text FROM tag_map JOIN tags ON tag_map.tag_id = tags.tag_id
+ORDER BY tags.tag_text
+"#,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for (jot_id, text) in rows {
+        tags.entry(jot_id).or_default().push(text);
+    }
+
+    Ok(tags)
+}
+
+/// The text of the tags of just the given jots, alphabetically, by jot ID; for when only a page of
+/// jots is wanted.
+pub async fn get_tags_for_jots(
+    conn: &SqlitePool,
+    ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<String>>> {
+    let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+
+    for batch in ids.chunks(ID_BATCH) {
+        let sql = format!(
+            r#"
+SELECT tag_map.jot_id, tags.tag_text FROM tag_map JOIN tags ON tag_map.tag_id = tags.tag_id
+WHERE tag_map.jot_id IN ({})
+ORDER BY tags.tag_text
+"#,
+            placeholders(batch.len())
+        );
+        let mut rows = query_as::<_, (Uuid, String)>(&sql);
+        for id in batch {
+            rows = rows.bind(id);
+        }
+
+        for (jot_id, text) in rows.fetch_all(conn).await? {
+            tags.entry(jot_id).or_default().push(text);
+        }
+    }
+
+    Ok(tags)
+}
+
+/// Every tag alias, ordered by alias.
+pub async fn get_tag_aliases(conn: &SqlitePool) -> Result<Vec<TagAlias>> {
+    let aliases = query_as(
+        r#"
+SELECT * FROM tag_aliases ORDER BY alias_text
+"#,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(aliases)
+}
+
+/// Give a tag new text, and so a new ID, carrying its jots and aliases along with it. The old text
+/// becomes an alias for the new, so that it keeps working for new jots and a merge with a database
+/// that still has the old tag folds it in rather than bringing it back. Fails if a tag with the new
+/// text already exists; use `merge_tags` for that.
+pub async fn rename_tag(
+    pool: &SqlitePool,
+    identity: &Identity,
+    old: &str,
+    new: &str,
+) -> Result<()> {
+    let (old, new) = (normalize(old), normalize(new));
+    let mut tx = pool.begin().await?;
+
+    let tag = match get_tag(&mut tx, identity, &old).await? {
+        Some(tag) => tag,
+        None => return Err(Error::NotFound(format!("no tag '{}'", old))),
+    };
+    if get_tag(&mut tx, identity, &new).await?.is_some() {
+        return Err(Error::Invalid(format!(
+            "tag '{}' already exists; merge into it instead",
+            new
+        )));
+    }
+
+    let new_tag = models::Tag::new(
+        new.clone(),
+        mk_tag_id(identity, &new),
+        tag.device(),
+        tag.created(),
+        0,
+        identity.author_id,
+    );
+    let _ = new_tag.as_insert().execute(&mut tx).await?;
+
+    retarget(&mut tx, tag.id(), new_tag.id()).await?;
+    record_alias(&mut tx, identity, &old, new_tag.id()).await?;
+    rescore_tags(&mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Fold the tag `from` into the tag `into`: every jot tagged `from` is tagged `into` instead, and
+/// `from` becomes an alias for `into` so that it keeps working for new jots, and so that a merge
+/// with a database that still has `from` folds it in too.
+pub async fn merge_tags(
+    pool: &SqlitePool,
+    identity: &Identity,
+    from: &str,
+    into: &str,
+) -> Result<()> {
+    let (from, into) = (normalize(from), normalize(into));
+    if from == into {
+        return Err(Error::Invalid(format!(
+            "can't merge tag '{}' into itself",
+            from
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let from_tag = match get_tag(&mut tx, identity, &from).await? {
+        Some(tag) => tag,
+        None => return Err(Error::NotFound(format!("no tag '{}'", from))),
+    };
+    let into_tag = match get_tag(&mut tx, identity, &into).await? {
+        Some(tag) => tag,
+        None => {
+            return Err(Error::NotFound(format!(
+                "no tag '{}'; rename '{}' instead",
+                into, from
+            )))
+        }
+    };
+
+    retarget(&mut tx, from_tag.id(), into_tag.id()).await?;
+    record_alias(&mut tx, identity, &from, into_tag.id()).await?;
+
+    rescore_tags(&mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Make `alias` another name for `tag`, so that jots inserted with `alias` get `tag` instead. The
+/// tag is created if it doesn't exist yet.
+pub async fn alias_tag(
+    pool: &SqlitePool,
+    identity: &Identity,
+    alias: &str,
+    tag: &str,
+) -> Result<()> {
+    let alias = normalize(alias);
+    let mut tx = pool.begin().await?;
+
+    let tag = resolve_alias(&mut tx, identity, tag).await?;
+    if alias == tag {
+        return Err(Error::Invalid(format!(
+            "can't make '{}' an alias of itself",
+            alias
+        )));
+    }
+    if get_tag(&mut tx, identity, &alias).await?.is_some() {
+        return Err(Error::Invalid(format!(
+            "'{}' is already a tag; merge it into '{}' instead",
+            alias, tag
+        )));
+    }
+
+    let tag_id = match get_tag(&mut tx, identity, &tag).await? {
+        Some(existing) => existing.id(),
+        None => {
+            let new_tag = models::Tag::new(
+                tag.clone(),
+                mk_tag_id(identity, &tag),
+                identity.device_id,
+                Some(chrono::Utc::now()),
+                0,
+                identity.author_id,
+            );
+            let _ = new_tag.as_insert().execute(&mut tx).await?;
+            new_tag.id()
+        }
+    };
+
+    record_alias(&mut tx, identity, &alias, tag_id).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Recount every tag's score from the jots it's actually mapped to, returning how many tags were
+/// off.
+pub async fn rescore_all_tags(pool: &SqlitePool) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    let changed = rescore_tags(&mut tx).await?;
+    tx.commit().await?;
+
+    Ok(changed)
+}
+
+// Set every tag's score to the number of jots it's mapped to, returning how many tags changed.
+pub(crate) async fn rescore_tags(conn: &mut SqliteConnection) -> Result<u64> {
+    let changed = query(
+        r#"
+UPDATE tags SET score = (SELECT COUNT(*) FROM tag_map WHERE tag_map.tag_id = tags.tag_id)
+WHERE score != (SELECT COUNT(*) FROM tag_map WHERE tag_map.tag_id = tags.tag_id)
+"#,
+    )
+    .execute(conn)
+    .await?
+    .rows_affected();
+
+    Ok(changed)
+}
+
+/// The tag text that `tag` stands for: the tag it's an alias of, or itself.
+pub(crate) async fn resolve_alias(
+    conn: &mut SqliteConnection,
+    identity: &Identity,
+    tag: &str,
+) -> Result<String> {
+    let tag = normalize(tag);
+    let resolved: Option<String> = query_scalar(
+        r#"
+SELECT tags.tag_text FROM tag_aliases JOIN tags ON tags.tag_id = tag_aliases.tag_id
+WHERE tag_aliases.alias_text = ?1
+ORDER BY tag_aliases.alias_id = ?2 DESC
+LIMIT 1
+"#,
+    )
+    .bind(&tag)
+    .bind(mk_tag_id(identity, &tag))
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(resolved.unwrap_or(tag))
+}
+
+// The tag with the given text, preferring our own to one made by someone else.
+pub(crate) async fn get_tag(
+    conn: &mut SqliteConnection,
+    identity: &Identity,
+    text: &str,
+) -> Result<Option<Tag>> {
+    let tag = query_as("SELECT * FROM tags WHERE tag_text = ?1 ORDER BY tag_id = ?2 DESC LIMIT 1")
+        .bind(text)
+        .bind(mk_tag_id(identity, text))
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(tag)
+}
+
+// Make `alias` stand for the tag `tag_id`, replacing whatever it stood for before.
+async fn record_alias(
+    conn: &mut SqliteConnection,
+    identity: &Identity,
+    alias: &str,
+    tag_id: Uuid,
+) -> Result<()> {
+    let alias_id = mk_tag_id(identity, alias);
+    let _ = query("DELETE FROM tag_aliases WHERE alias_id = ?1")
+        .bind(alias_id)
+        .execute(&mut *conn)
+        .await?;
+    let new_alias = models::TagAlias::new(
+        alias.to_owned(),
+        alias_id,
+        tag_id,
+        identity.device_id,
+        Some(chrono::Utc::now()),
+    );
+    let _ = new_alias.as_insert().execute(&mut *conn).await?;
+
+    Ok(())
+}
+
+/// Fold every tag whose text is an alias into the tag the alias stands for, as `merge_tags` would
+/// have, returning how many tags were folded away. A merge calls this so that a tag renamed or
+/// merged away on one side doesn't come back from the other.
+pub(crate) async fn fold_aliased_tags(conn: &mut SqliteConnection) -> Result<u64> {
+    let mut folded = 0;
+    // every fold drops a tag, so this ends even if two aliases point at each other's tags
+    loop {
+        let aliased: Option<(Uuid, Uuid)> = query_as(
+            r#"
+SELECT tags.tag_id, tag_aliases.tag_id FROM tags
+JOIN tag_aliases ON tag_aliases.alias_text = tags.tag_text
+WHERE tags.tag_id != tag_aliases.tag_id
+  AND tag_aliases.tag_id IN (SELECT tag_id FROM tags)
+ORDER BY tag_aliases.alias_date DESC
+LIMIT 1
+"#,
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        match aliased {
+            Some((from, to)) => retarget(conn, from, to).await?,
+            None => return Ok(folded),
+        }
+        folded += 1;
+    }
+}
+
+// Move the jots and aliases of tag `from` over to tag `to`, then drop `from`. Mapping IDs are
+// derived from the tag ID, so the mappings are recreated rather than updated.
+async fn retarget(conn: &mut SqliteConnection, from: Uuid, to: Uuid) -> Result<()> {
+    let mappings: Vec<(Uuid, Option<crate::StarDate>)> =
+        query_as("SELECT jot_id, mapping_date FROM tag_map WHERE tag_id = ?1")
+            .bind(from)
+            .fetch_all(&mut *conn)
+            .await?;
+
+    for (jot_id, date) in mappings {
+        let mapping = models::Mapping::new(mk_mapping_id(&jot_id, &to), to, jot_id, date);
+        let _ = mapping.as_insert_or_ignore().execute(&mut *conn).await?;
+    }
+
+    let _ = query("UPDATE tag_aliases SET tag_id = ?1 WHERE tag_id = ?2")
+        .bind(to)
+        .bind(from)
+        .execute(&mut *conn)
+        .await?;
+
+    // the old mappings go with the tag
+    let _ = query("DELETE FROM tags WHERE tag_id = ?1")
+        .bind(from)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+fn normalize(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
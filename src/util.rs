@@ -1,71 +1,405 @@
-use std::path::Path;
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use mime::Mime;
 
 use serde::{Deserialize, Serialize};
 
-use super::Uuid;
+use super::{Error, Result, Uuid};
 
-const NAMESPACE_JOT: &str = "930ccacb-5523-4be7-8045-f033465dae8f"; // v4 UUID used for constructing v5 UUIDs
+const NAMESPACE_JOT: Uuid = Uuid::from_u128(0x930ccacb_5523_4be7_8045_f033465dae8f); // v4 UUID used for constructing v5 UUIDs
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct JotlogConfig {
     pub dev_id: String,
+    /// Who writes the jots, as opposed to which device they're written on. Copy it to every device
+    /// you use so that they agree on IDs; a config without one keeps the IDs it always had.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_id: Option<String>,
+    pub db_file: String,
+    /// Tags given to every new jot in the default journal.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// A command that prints the passphrase of the default journal, if it's encrypted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase_command: Option<String>,
+    /// Other journals, by name, eg, a `[profiles.work]` table with its own `db_file` and `tags`.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// The keys for `joenal-gui`'s commands.
+    #[serde(default)]
+    pub shortcuts: Shortcuts,
+}
+
+/// The keys that run each of `joenal-gui`'s commands, like "j", "ArrowDown" or "Ctrl+K". Keys with
+/// Ctrl, Alt or Meta work anywhere; the rest only when no text field has the focus.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct Shortcuts {
+    pub next_jot: Vec<String>,
+    pub previous_jot: Vec<String>,
+    pub search: Vec<String>,
+    pub new_jot: Vec<String>,
+    pub edit_jot: Vec<String>,
+    pub palette: Vec<String>,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        let keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect();
+
+        Shortcuts {
+            next_jot: keys(&["j", "ArrowDown"]),
+            previous_jot: keys(&["k", "ArrowUp"]),
+            search: keys(&["/"]),
+            new_jot: keys(&["n"]),
+            edit_jot: keys(&["e"]),
+            palette: keys(&["Ctrl+K"]),
+        }
+    }
+}
+
+/// A journal: the database it lives in, the tags given to every jot written to it, and how to get
+/// its passphrase if it's encrypted.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Profile {
     pub db_file: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase_command: Option<String>,
+}
+
+impl Profile {
+    /// The tags a new jot in this journal gets: the ones given for it, then the profile's own, or
+    /// "untagged" if that leaves none.
+    pub fn jot_tags(&self, mut tags: Vec<String>) -> Vec<String> {
+        for tag in self.tags.iter() {
+            let tag = tag.trim().to_lowercase();
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        if tags.is_empty() {
+            tags.push("untagged".to_owned());
+        }
+        tags
+    }
+}
+
+/// Who is writing: this device, and the author whose jots it writes. It's read from the
+/// configuration once and handed to everything that makes IDs, so that a whole run makes them in
+/// the same namespace.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Identity {
+    pub device_id: Uuid,
+    pub author_id: Option<Uuid>,
+}
+
+impl Identity {
+    /// The namespace jot IDs are made in: the author's, so that all their devices make the same ID
+    /// for the same jot, or without an author the device's, so that older configs keep their IDs.
+    pub fn jot_root(&self) -> Uuid {
+        match self.author_id {
+            Some(author_id) => Uuid::new_v5(&mk_jot_ns_uuid(author_id.as_bytes()), b"jots"),
+            None => Uuid::new_v5(&NAMESPACE_JOT, self.device_id.as_bytes()),
+        }
+    }
+
+    // The namespace for IDs that belong to whoever is writing: the jot namespace itself if there's
+    // no author, so that older configs keep their IDs.
+    fn author_ns(&self) -> Uuid {
+        match self.author_id {
+            Some(author_id) => mk_jot_ns_uuid(author_id.as_bytes()),
+            None => NAMESPACE_JOT,
+        }
+    }
+}
+
+impl JotlogConfig {
+    /// This device and its author.
+    pub fn identity(&self) -> Result<Identity> {
+        let author_id = match self.author_id.as_ref() {
+            Some(author_id) => Some(Uuid::parse_str(author_id)?),
+            None => None,
+        };
+
+        Ok(Identity {
+            device_id: Uuid::parse_str(&self.dev_id)?,
+            author_id,
+        })
+    }
+
+    /// The profile with the given name, or the default journal if `name` is `None`.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        match name {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::Config(format!("no profile named '{}'", name))),
+            None => Ok(Profile {
+                db_file: self.db_file.clone(),
+                tags: self.tags.clone(),
+                passphrase_command: self.passphrase_command.clone(),
+            }),
+        }
+    }
 }
 
 impl Default for JotlogConfig {
     fn default() -> Self {
         let dev_id = Uuid::new_v4();
-        let db_file = Path::new(&std::env::var("HOME").unwrap()).join(".joenal.sqlite");
+        let author_id = Uuid::new_v4();
+        let db_file =
+            Path::new(&std::env::var_os("HOME").unwrap_or_default()).join(".joenal.sqlite");
 
         JotlogConfig {
             dev_id: dev_id.to_hyphenated().to_string(),
-            db_file: db_file.to_str().unwrap().to_owned(),
+            author_id: Some(author_id.to_hyphenated().to_string()),
+            db_file: db_file.to_string_lossy().into_owned(),
+            tags: Vec::new(),
+            passphrase_command: None,
+            profiles: BTreeMap::new(),
+            shortcuts: Shortcuts::default(),
         }
     }
 }
 
-pub fn get_config() -> JotlogConfig {
-    confy::load("joenal").unwrap()
+pub fn get_config() -> Result<JotlogConfig> {
+    Ok(confy::load("joenal")?)
 }
 
-pub fn get_device_id() -> Uuid {
-    let dev_id = get_config().dev_id;
-    Uuid::parse_str(&dev_id).unwrap()
+/// The journal to use: the profile with the given name, or the default journal, with its database
+/// swapped for `db` if one is given.
+pub fn get_profile(name: Option<&str>, db: Option<&str>) -> Result<Profile> {
+    let mut profile = get_config()?.profile(name)?;
+    if let Some(db) = db {
+        profile.db_file = db.to_owned();
+    }
+
+    Ok(profile)
 }
 
-pub fn get_joenal_root() -> Uuid {
-    let dev_id = get_config().dev_id;
-    let dev_id = Uuid::parse_str(&dev_id).unwrap();
-    mk_jot_ns_uuid(dev_id.as_bytes())
+pub fn get_identity() -> Result<Identity> {
+    get_config()?.identity()
 }
 
-// TODO: incorporate user identity into jot identity
-pub(crate) fn mk_jot_ns_uuid(data: &[u8]) -> Uuid {
-    let jot_ns = Uuid::parse_str(NAMESPACE_JOT).unwrap();
+pub fn get_device_id() -> Result<Uuid> {
+    Ok(get_identity()?.device_id)
+}
 
-    Uuid::new_v5(&jot_ns, data)
+pub(crate) fn mk_jot_ns_uuid(data: &[u8]) -> Uuid {
+    Uuid::new_v5(&NAMESPACE_JOT, data)
 }
 
 pub fn fmt_uuid(u: Uuid) -> Vec<u8> {
     u.as_bytes().to_vec()
 }
 
-// TODO: incorporate user identity into tag identity
-pub fn mk_tag_id(tag: &str) -> Uuid {
-    mk_jot_ns_uuid(tag.as_bytes())
+/// Split a tag like "host:laptop" into its namespace and value. A tag only has a namespace if the
+/// part before the first colon is a single word and something follows the colon; otherwise the
+/// whole tag is the value.
+pub fn split_tag(tag: &str) -> (Option<&str>, &str) {
+    match tag.find(':') {
+        Some(i) if i > 0 && i + 1 < tag.len() && !tag[..i].contains(char::is_whitespace) => {
+            (Some(&tag[..i]), &tag[i + 1..])
+        }
+        _ => (None, tag),
+    }
 }
 
-pub(crate) fn mk_jot_id(jot: &crate::RawJot) -> Uuid {
-    let joenal_root = get_joenal_root();
-    let content = [
-        jot.content.as_bytes(),
-        jot.creation_date.to_rfc3339().as_bytes(),
-    ]
-    .concat();
-    Uuid::new_v5(&joenal_root, &content)
+pub fn mk_tag_id(identity: &Identity, tag: &str) -> Uuid {
+    Uuid::new_v5(&identity.author_ns(), tag.as_bytes())
+}
+
+pub(crate) fn mk_jot_id(identity: &Identity, jot: &crate::RawJot) -> Uuid {
+    mk_content_id(identity, jot.content.as_bytes(), &jot.creation_date)
+}
+
+pub(crate) fn mk_content_id(
+    identity: &Identity,
+    content: &[u8],
+    creation_date: &crate::StarDate,
+) -> Uuid {
+    let content = [content, creation_date.to_rfc3339().as_bytes()].concat();
+    Uuid::new_v5(&identity.jot_root(), &content)
 }
 
 pub fn mk_mapping_id(jot_id: &Uuid, tag_id: &Uuid) -> Uuid {
     let data = [*jot_id.as_bytes(), *tag_id.as_bytes()].concat();
     mk_jot_ns_uuid(&data)
 }
+
+pub fn mk_link_id(from: &Uuid, to: &Uuid, link_type: crate::LinkType) -> Uuid {
+    let data = [
+        from.as_bytes().as_ref(),
+        to.as_bytes().as_ref(),
+        link_type.as_str().as_bytes(),
+    ]
+    .concat();
+    mk_jot_ns_uuid(&data)
+}
+
+pub fn mk_revision_id(jot_id: &Uuid, content: &[u8], date: &crate::StarDate) -> Uuid {
+    let data = [
+        jot_id.as_bytes().as_ref(),
+        content,
+        date.to_rfc3339().as_bytes(),
+    ]
+    .concat();
+    mk_jot_ns_uuid(&data)
+}
+
+/// Create a new file in the temporary directory with a random name ending in `suffix`, readable
+/// and writable only by the current user. It's never one that already existed, so nobody else can
+/// have put it there first.
+pub fn create_temp_file(suffix: &str) -> Result<(PathBuf, File)> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    loop {
+        let name = format!("joenal-{}{}", Uuid::new_v4().to_simple(), suffix);
+        let path = std::env::temp_dir().join(name);
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Remove a scratch SQLite database made with `create_temp_file`, along with any write-ahead log
+/// and shared-memory files it left next to it.
+pub(crate) fn remove_db_file(path: &Path) {
+    for suffix in ["", "-wal", "-shm"].iter() {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        let _ = std::fs::remove_file(file);
+    }
+}
+
+/// Work out the MIME type of a file from its leading bytes, falling back to its extension. Files
+/// that turn out to be UTF-8 text get a charset. The file's name is kept as a `name` parameter.
+pub fn sniff_mime(path: &Path, bytes: &[u8]) -> String {
+    let guess = mime_guess::from_path(path).first();
+
+    let mime: Mime = match infer::get(bytes) {
+        Some(kind) => kind
+            .mime_type()
+            .parse()
+            .unwrap_or(mime::APPLICATION_OCTET_STREAM),
+        None => match (std::str::from_utf8(bytes), guess) {
+            (Ok(_), Some(g)) if g.type_() == mime::TEXT => {
+                format!("{}; charset=utf-8", g.essence_str())
+                    .parse()
+                    .unwrap()
+            }
+            (Ok(_), None) => mime::TEXT_PLAIN_UTF_8,
+            (_, Some(g)) => g,
+            (Err(_), None) => mime::APPLICATION_OCTET_STREAM,
+        },
+    };
+
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => {
+            let name: String = name.chars().filter(|c| *c != '"' && *c != '\\').collect();
+            format!("{}; name=\"{}\"", mime, name)
+        }
+        None => mime.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jot_root(author_id: Option<Uuid>, device_id: Uuid) -> Uuid {
+        Identity {
+            device_id,
+            author_id,
+        }
+        .jot_root()
+    }
+
+    #[test]
+    fn jot_root_follows_the_author() {
+        let author = Some(Uuid::new_v4());
+        let (laptop, phone) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert_eq!(jot_root(author, laptop), jot_root(author, phone));
+        assert_ne!(
+            jot_root(author, laptop),
+            jot_root(Some(Uuid::new_v4()), laptop)
+        );
+    }
+
+    #[test]
+    fn jot_root_without_an_author_is_the_device() {
+        let (laptop, phone) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert_ne!(jot_root(None, laptop), jot_root(None, phone));
+        assert_eq!(
+            jot_root(None, laptop),
+            Uuid::new_v5(&NAMESPACE_JOT, laptop.as_bytes())
+        );
+    }
+
+    #[test]
+    fn identity_from_the_config() {
+        let mut config = JotlogConfig::default();
+        let identity = config.identity().unwrap();
+        assert_eq!(
+            identity.device_id.to_hyphenated().to_string(),
+            config.dev_id
+        );
+        assert!(identity.author_id.is_some());
+
+        config.author_id = None;
+        assert_eq!(config.identity().unwrap().author_id, None);
+        config.dev_id = "not a uuid".to_owned();
+        assert!(config.identity().is_err());
+    }
+
+    #[test]
+    fn jot_tags_add_the_profile_tags() {
+        let profile = Profile {
+            db_file: String::new(),
+            tags: vec!["Work".to_owned(), " ".to_owned(), "notes".to_owned()],
+            passphrase_command: None,
+        };
+        assert_eq!(
+            profile.jot_tags(vec!["notes".to_owned(), "idea".to_owned()]),
+            vec!["notes", "idea", "work"]
+        );
+
+        let bare = Profile {
+            tags: Vec::new(),
+            ..profile
+        };
+        assert_eq!(bare.jot_tags(Vec::new()), vec!["untagged"]);
+        assert_eq!(bare.jot_tags(vec!["idea".to_owned()]), vec!["idea"]);
+    }
+
+    #[test]
+    fn split_tag_namespaces() {
+        assert_eq!(split_tag("host:laptop"), (Some("host"), "laptop"));
+        assert_eq!(split_tag("pwd:/home/me:x"), (Some("pwd"), "/home/me:x"));
+        assert_eq!(split_tag("host:*"), (Some("host"), "*"));
+    }
+
+    #[test]
+    fn split_tag_without_a_namespace() {
+        assert_eq!(split_tag("laptop"), (None, "laptop"));
+        assert_eq!(split_tag(":laptop"), (None, ":laptop"));
+        assert_eq!(split_tag("host:"), (None, "host:"));
+        assert_eq!(split_tag("to do: this"), (None, "to do: this"));
+    }
+}